@@ -0,0 +1,289 @@
+use std::{collections::HashMap, hash::Hash, rc::Rc};
+use crate::texture::Texture;
+
+// stays valid until the texture is unloaded, after that it never refers to another texture
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TextureHandle {
+    index: u32,
+    generation: u32
+}
+
+// what draw calls accept as a texture: a path or name, or a handle
+#[derive(Clone, Copy, Debug)]
+pub enum TextureKey<'a> {
+    Name(&'a str),
+    Handle(TextureHandle)
+}
+
+impl<'a> From<&'a str> for TextureKey<'a> {
+    fn from(name: &'a str) -> Self {
+        TextureKey::Name(name)
+    }
+}
+
+impl<'a> From<&'a String> for TextureKey<'a> {
+    fn from(name: &'a String) -> Self {
+        TextureKey::Name(name)
+    }
+}
+
+impl From<TextureHandle> for TextureKey<'_> {
+    fn from(handle: TextureHandle) -> Self {
+        TextureKey::Handle(handle)
+    }
+}
+
+impl From<&TextureHandle> for TextureKey<'_> {
+    fn from(handle: &TextureHandle) -> Self {
+        TextureKey::Handle(*handle)
+    }
+}
+
+struct Slot<T> {
+    texture: Rc<T>,
+    references: u32,
+    names: Vec<String>,
+    // the crate's own textures, like font atlases, have no name and can't be unloaded by the app
+    internal: bool
+}
+
+// every loaded texture, addressed by handle or by any of its names. generic so the bookkeeping can be
+// tested without a gl context
+pub(crate) struct Assets<T = Texture> {
+    slots: Vec<Option<Slot<T>>>,
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    names: HashMap<String, TextureHandle>
+}
+
+impl<T> Assets<T> {
+    pub fn new() -> Self {
+        Self { slots: Vec::new(), generations: Vec::new(), free: Vec::new(), names: HashMap::new() }
+    }
+
+    // the new texture starts out with one reference
+    pub fn insert(&mut self, name: &str, texture: Rc<T>) -> TextureHandle {
+        let handle = self.insert_slot(Slot { texture, references: 1, names: Vec::new(), internal: false });
+        self.alias(handle, name);
+        handle
+    }
+
+    // kept out of the names apps use, so loading a texture with the same name never replaces it
+    pub fn insert_internal(&mut self, texture: Rc<T>) -> TextureHandle {
+        self.insert_slot(Slot { texture, references: 1, names: Vec::new(), internal: true })
+    }
+
+    fn insert_slot(&mut self, slot: Slot<T>) -> TextureHandle {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize] = Some(slot);
+                index
+            },
+            None => {
+                self.slots.push(Some(slot));
+                self.generations.push(0);
+                self.slots.len() as u32 - 1
+            }
+        };
+        TextureHandle { index, generation: self.generations[index as usize] }
+    }
+
+    fn slot(&self, handle: TextureHandle) -> Option<&Slot<T>> {
+        if self.generations.get(handle.index as usize) != Some(&handle.generation) {
+            return None
+        }
+        self.slots[handle.index as usize].as_ref()
+    }
+
+    fn slot_mut(&mut self, handle: TextureHandle) -> Option<&mut Slot<T>> {
+        if self.generations.get(handle.index as usize) != Some(&handle.generation) {
+            return None
+        }
+        self.slots[handle.index as usize].as_mut()
+    }
+
+    pub fn contains(&self, handle: TextureHandle) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    pub fn is_internal(&self, handle: TextureHandle) -> bool {
+        self.slot(handle).is_some_and(|slot| slot.internal)
+    }
+
+    pub fn get(&self, handle: TextureHandle) -> Option<Rc<T>> {
+        self.slot(handle).map(|slot| slot.texture.clone())
+    }
+
+    pub fn handle(&self, name: &str) -> Option<TextureHandle> {
+        self.names.get(name).copied()
+    }
+
+    // a name can only point at one texture, aliasing it again moves it
+    pub fn alias(&mut self, handle: TextureHandle, name: &str) -> bool {
+        if !self.contains(handle) || self.is_internal(handle) {
+            return false
+        }
+        if let Some(previous) = self.names.insert(name.to_string(), handle) {
+            if let Some(slot) = self.slot_mut(previous) {
+                slot.names.retain(|slot_name| slot_name != name);
+            }
+        }
+        self.slot_mut(handle).unwrap().names.push(name.to_string());
+        true
+    }
+
+    pub fn retain(&mut self, handle: TextureHandle) {
+        if let Some(slot) = self.slot_mut(handle) {
+            slot.references += 1;
+        }
+    }
+
+    // drops one reference, returns true if that was the last one and the texture is gone
+    pub fn release(&mut self, handle: TextureHandle) -> bool {
+        let slot = match self.slot_mut(handle) {
+            Some(slot) => slot,
+            None => return false
+        };
+        slot.references = slot.references.saturating_sub(1);
+        if slot.references > 0 {
+            return false
+        }
+
+        let slot = self.slots[handle.index as usize].take().unwrap();
+        for name in slot.names {
+            self.names.remove(&name);
+        }
+        self.generations[handle.index as usize] += 1;
+        self.free.push(handle.index);
+        true
+    }
+
+    // returns the texture that was replaced
+    pub fn replace(&mut self, handle: TextureHandle, texture: Rc<T>) -> Option<Rc<T>> {
+        self.slot_mut(handle).map(|slot| std::mem::replace(&mut slot.texture, texture))
+    }
+}
+
+impl Assets {
+    // every texture that was loaded from a file, with that file's path
+    pub fn file_paths(&self) -> Vec<(TextureHandle, String)> {
+        let mut file_paths = Vec::new();
+        for (index, slot) in self.slots.iter().enumerate() {
+            if let Some(path) = slot.as_ref().and_then(|slot| slot.texture.path.clone()) {
+                file_paths.push((TextureHandle { index: index as u32, generation: self.generations[index] }, path));
+            }
+        }
+        file_paths
+    }
+}
+
+// batches kept in creation order, which is also their draw order, with constant time lookup by key
+pub(crate) struct BatchMap<K, T> {
+    keys: Vec<K>,
+    batches: Vec<T>,
+    index: HashMap<K, usize>
+}
+
+impl<K: Copy + Eq + Hash, T> BatchMap<K, T> {
+    pub fn new() -> Self {
+        Self { keys: Vec::new(), batches: Vec::new(), index: HashMap::new() }
+    }
+
+    pub fn get_or_insert_with(&mut self, key: K, create: impl FnOnce() -> T) -> &mut T {
+        let index = match self.index.get(&key) {
+            Some(&index) => index,
+            None => {
+                self.keys.push(key);
+                self.batches.push(create());
+                self.index.insert(key, self.batches.len() - 1);
+                self.batches.len() - 1
+            }
+        };
+        &mut self.batches[index]
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.batches.iter_mut()
+    }
+
+    pub fn retain(&mut self, keep: impl Fn(&K) -> bool) {
+        let mut index = 0;
+        while index < self.keys.len() {
+            if keep(&self.keys[index]) {
+                index += 1;
+            }
+            else {
+                self.keys.remove(index);
+                self.batches.remove(index);
+            }
+        }
+        self.index = self.keys.iter().enumerate().map(|(index, key)| (*key, index)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets() -> Assets<&'static str> {
+        Assets::new()
+    }
+
+    #[test]
+    fn a_handle_is_stale_after_unloading() {
+        let mut assets = assets();
+        let handle = assets.insert("grass.png", Rc::new("grass"));
+        assert!(assets.release(handle));
+        assert!(!assets.contains(handle));
+        assert!(assets.get(handle).is_none());
+        assert_eq!(assets.handle("grass.png"), None);
+        // releasing it again doesn't touch anything else
+        assert!(!assets.release(handle));
+    }
+
+    #[test]
+    fn an_alias_keeps_the_texture_alive() {
+        let mut assets = assets();
+        let handle = assets.insert("grass.png", Rc::new("grass"));
+        assert!(assets.alias(handle, "grass"));
+        assets.retain(handle);
+
+        // the first unload only drops the reference the alias added
+        assert!(!assets.release(handle));
+        assert_eq!(assets.handle("grass"), Some(handle));
+        assert_eq!(assets.get(handle).as_deref(), Some(&"grass"));
+
+        assert!(assets.release(handle));
+        assert_eq!(assets.handle("grass"), None);
+        assert_eq!(assets.handle("grass.png"), None);
+    }
+
+    #[test]
+    fn a_freed_slot_is_reused_with_a_new_generation() {
+        let mut assets = assets();
+        let old = assets.insert("grass.png", Rc::new("grass"));
+        assets.release(old);
+        let new = assets.insert("stone.png", Rc::new("stone"));
+
+        assert_eq!(new.index, old.index);
+        assert_ne!(new.generation, old.generation);
+        // the old handle never refers to the texture that took over its slot
+        assert!(assets.get(old).is_none());
+        assert!(!assets.release(old));
+        assert_eq!(assets.get(new).as_deref(), Some(&"stone"));
+    }
+
+    #[test]
+    fn internal_textures_stay_out_of_the_names() {
+        let mut assets = assets();
+        let atlas = assets.insert_internal(Rc::new("atlas"));
+        assert!(assets.is_internal(atlas));
+        assert!(!assets.alias(atlas, "bitmap font"));
+        assert_eq!(assets.handle("bitmap font"), None);
+
+        // a texture the app names like it is a different one
+        let texture = assets.insert("bitmap font", Rc::new("texture"));
+        assert!(!assets.is_internal(texture));
+        assert_eq!(assets.get(atlas).as_deref(), Some(&"atlas"));
+    }
+}
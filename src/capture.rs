@@ -0,0 +1,155 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use glium::{texture::{pixel_buffer::PixelBuffer, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat}, uniforms::MagnifySamplerFilter, Display, Frame, Surface};
+
+#[derive(Debug)]
+pub enum CaptureError {
+    // the texture the frame is copied into couldn't be created
+    Texture(glium::texture::TextureCreationError),
+    // the pixels read back don't fill an image of their width and height
+    Size(u32, u32),
+    // the frame couldn't be copied before it was finished
+    NoFrame,
+    // the pixel buffer a frame was read back into couldn't be mapped
+    Read(glium::buffer::ReadError),
+    // the thread writing a recording panicked
+    Worker,
+    Save(String, image::ImageError),
+    Io(String, std::io::Error)
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::Texture(error) => write!(f, "could not create a texture to copy the frame into: {}", error),
+            CaptureError::Size(width, height) => write!(f, "the frame read back doesn't fill {}x{} pixels", width, height),
+            CaptureError::NoFrame => write!(f, "the frame couldn't be copied for a capture"),
+            CaptureError::Read(error) => write!(f, "could not read the frame back: {}", error),
+            CaptureError::Worker => write!(f, "the recording thread panicked"),
+            CaptureError::Save(path, error) => write!(f, "could not save '{}': {}", path, error),
+            CaptureError::Io(path, error) => write!(f, "could not create '{}': {}", path, error)
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Texture(error) => Some(error),
+            CaptureError::Read(error) => Some(error),
+            CaptureError::Size(_, _) | CaptureError::NoFrame | CaptureError::Worker => None,
+            CaptureError::Save(_, error) => Some(error),
+            CaptureError::Io(_, error) => Some(error)
+        }
+    }
+}
+
+// copies the frame into texture before it's finished, the window's own buffers are undefined once they're
+// swapped. a smaller size scales the frame down on the gpu so less has to be read back. the texture is only
+// created again when the size changed
+pub(crate) fn copy_frame(display: &Display, frame: &Frame, texture: &mut Option<Texture2d>, size: (u32, u32)) -> Result<(), CaptureError> {
+    if texture.as_ref().is_none_or(|texture| texture.dimensions() != size) {
+        *texture = Some(Texture2d::empty_with_format(display, UncompressedFloatFormat::U8U8U8U8, MipmapsOption::NoMipmap, size.0, size.1).map_err(CaptureError::Texture)?);
+    }
+    let filter = match frame.get_dimensions() == size {
+        true => MagnifySamplerFilter::Nearest,
+        false => MagnifySamplerFilter::Linear
+    };
+    frame.fill(&texture.as_ref().unwrap().as_surface(), filter);
+    Ok(())
+}
+
+pub(crate) fn read_frame(texture: &Texture2d) -> Result<image::RgbaImage, CaptureError> {
+    to_image(texture.read())
+}
+
+pub(crate) fn read_pixel_buffer(pixel_buffer: &PixelBuffer<(u8, u8, u8, u8)>) -> Result<image::RgbaImage, CaptureError> {
+    to_image(pixel_buffer.read_as_texture_2d().map_err(CaptureError::Read)?)
+}
+
+// gl hands the pixels over bottom row first so they're flipped to match image's layout
+fn to_image(raw: RawImage2d<u8>) -> Result<image::RgbaImage, CaptureError> {
+    let mut image = image::RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned()).ok_or(CaptureError::Size(raw.width, raw.height))?;
+    image::imageops::flip_vertical_in_place(&mut image);
+    Ok(image)
+}
+
+// a screenshot asked for during a frame, taken once the frame is drawn
+pub(crate) enum PendingCapture {
+    Save(String),
+    Image(Box<dyn FnOnce(Result<image::RgbaImage, CaptureError>)>)
+}
+
+// the screenshots a window owes, its frame is only copied while there are some
+#[derive(Default)]
+pub(crate) struct Captures {
+    pending: Vec<PendingCapture>
+}
+
+impl Captures {
+    pub fn push(&mut self, capture: PendingCapture) {
+        self.pending.push(capture);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // hands every pending capture the frame read reads back, saved files and errors are logged
+    pub fn deliver(&mut self, read: impl Fn() -> Result<image::RgbaImage, CaptureError>) {
+        for capture in self.pending.drain(..) {
+            match capture {
+                PendingCapture::Save(path) => match read().and_then(|image| save(&image, &path)) {
+                    Ok(()) => log::info!("saved a screenshot to '{}'", path),
+                    Err(error) => log::error!("{}", error)
+                },
+                PendingCapture::Image(callback) => callback(read())
+            }
+        }
+    }
+}
+
+pub(crate) fn save(image: &image::RgbaImage, path: &str) -> Result<(), CaptureError> {
+    image.save(path).map_err(|error| CaptureError::Save(path.to_string(), error))
+}
+
+pub(crate) fn timestamped_path(prefix: &str, extension: &str) -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}_{}_{:03}.{}", prefix, since_epoch.as_secs(), since_epoch.subsec_millis(), extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn pending_captures_are_delivered_once() {
+        let mut captures = Captures::default();
+        assert!(!captures.is_pending());
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0 .. 2 {
+            let received = received.clone();
+            captures.push(PendingCapture::Image(Box::new(move |image| received.borrow_mut().push(image.map(|image| image.dimensions())))));
+        }
+        assert!(captures.is_pending());
+
+        captures.deliver(|| Ok(image::RgbaImage::new(4, 2)));
+        assert!(!captures.is_pending());
+        assert_eq!(received.borrow().iter().map(|result| *result.as_ref().unwrap()).collect::<Vec<_>>(), [(4, 2), (4, 2)]);
+
+        // nothing is owed anymore, so the next frame isn't copied or read
+        captures.deliver(|| panic!("read without a pending capture"));
+        assert_eq!(received.borrow().len(), 2);
+    }
+
+    #[test]
+    fn failed_reads_reach_the_callback() {
+        let mut captures = Captures::default();
+        let failed = Rc::new(RefCell::new(false));
+        let flag = failed.clone();
+        captures.push(PendingCapture::Image(Box::new(move |image| *flag.borrow_mut() = matches!(image, Err(CaptureError::NoFrame)))));
+        captures.deliver(|| Err(CaptureError::NoFrame));
+        assert!(*failed.borrow());
+    }
+}
@@ -0,0 +1,182 @@
+use std::rc::Rc;
+use glium::Rect;
+use crate::{texture::Texture, TextureError, TextureHandle};
+
+// how many bytes of rgba pixels a width * height texture holds, empty textures and ones whose size overflows
+// are rejected before anything is allocated
+pub(crate) fn byte_length(name: &str, width: u32, height: u32) -> Result<usize, TextureError> {
+    (width as usize).checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .filter(|length| *length > 0)
+        .ok_or_else(|| TextureError::Size(name.to_string(), width, height))
+}
+
+// a texture whose pixels live on the cpu, write to it and call upload to send the changes to the gpu.
+// it is registered under its name, so it's drawn with blit like any other texture
+pub struct DynamicTexture {
+    name: String,
+    handle: TextureHandle,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    // [x, y, width, height] of the pixels changed since the last upload
+    dirty: Option<[u32; 4]>,
+    texture: Rc<Texture>
+}
+
+impl DynamicTexture {
+    pub(crate) fn new(name: &str, handle: TextureHandle, width: u32, height: u32, texture: Rc<Texture>) -> Self {
+        Self {
+            name: name.to_string(),
+            handle,
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+            dirty: None,
+            texture
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn handle(&self) -> TextureHandle {
+        self.handle
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // rgba rows from top to bottom
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        self.mark_dirty(0, 0, self.width, self.height);
+        &mut self.pixels
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        if x >= self.width || y >= self.height {
+            return [0, 0, 0, 0]
+        }
+        let start = self.offset(x, y);
+        [self.pixels[start], self.pixels[start + 1], self.pixels[start + 2], self.pixels[start + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return
+        }
+        let start = self.offset(x, y);
+        self.pixels[start .. start + 4].copy_from_slice(&color);
+        self.mark_dirty(x, y, 1, 1);
+    }
+
+    pub fn fill(&mut self, color: [u8; 4]) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+        self.mark_dirty(0, 0, self.width, self.height);
+    }
+
+    // replaces every pixel, `pixels` has to hold width * height rgba values
+    pub fn write(&mut self, pixels: &[u8]) -> Result<(), TextureError> {
+        if pixels.len() != self.pixels.len() {
+            return Err(TextureError::Length(self.name.clone(), self.pixels.len(), pixels.len()))
+        }
+        self.pixels.copy_from_slice(pixels);
+        self.mark_dirty(0, 0, self.width, self.height);
+        Ok(())
+    }
+
+    // copies a width * height block of rgba pixels to x, y, clipping whatever falls outside
+    pub fn write_region(&mut self, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) -> Result<(), TextureError> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(TextureError::Length(self.name.clone(), expected, pixels.len()))
+        }
+        if x >= self.width || y >= self.height {
+            return Ok(())
+        }
+        let clipped_width = width.min(self.width - x);
+        let clipped_height = height.min(self.height - y);
+        let length = clipped_width as usize * 4;
+        for row in 0 .. clipped_height {
+            let source = row as usize * width as usize * 4;
+            let target = self.offset(x, y + row);
+            self.pixels[target .. target + length].copy_from_slice(&pixels[source .. source + length]);
+        }
+        self.mark_dirty(x, y, clipped_width, clipped_height);
+        Ok(())
+    }
+
+    // copies an image with its top left corner at x, y, fully transparent pixels are skipped
+    pub fn blit_image(&mut self, x: i32, y: i32, image: &image::RgbaImage) {
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + image.width() as i32).min(self.width as i32);
+        let bottom = (y + image.height() as i32).min(self.height as i32);
+        if left >= right || top >= bottom {
+            return
+        }
+
+        for target_y in top .. bottom {
+            for target_x in left .. right {
+                let color = image.get_pixel((target_x - x) as u32, (target_y - y) as u32).0;
+                if color[3] == 0 {
+                    continue
+                }
+                let start = self.offset(target_x as u32, target_y as u32);
+                self.pixels[start .. start + 4].copy_from_slice(&color);
+            }
+        }
+        self.mark_dirty(left as u32, top as u32, (right - left) as u32, (bottom - top) as u32);
+    }
+
+    // index of the first byte of the pixel at x, y
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    fn mark_dirty(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.dirty = Some(match self.dirty {
+            Some([dirty_x, dirty_y, dirty_width, dirty_height]) => {
+                let left = dirty_x.min(x);
+                let top = dirty_y.min(y);
+                let right = (dirty_x + dirty_width).max(x + width);
+                let bottom = (dirty_y + dirty_height).max(y + height);
+                [left, top, right - left, bottom - top]
+            },
+            None => [x, y, width, height]
+        });
+    }
+
+    // sends only the changed part of the texture to the gpu
+    pub fn upload(&mut self) {
+        let [x, y, width, height] = match self.dirty.take() {
+            Some(dirty) => dirty,
+            None => return
+        };
+        if width == 0 || height == 0 {
+            return
+        }
+
+        let mut region = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in y .. y + height {
+            let start = self.offset(x, row);
+            region.extend_from_slice(&self.pixels[start .. start + width as usize * 4]);
+        }
+
+        // textures are stored bottom row first
+        let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&region, (width, height));
+        self.texture.texture.write(Rect { left: x, bottom: self.height - (y + height), width, height }, image);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{CaptureError, FontError, TextureError};
+
+// everything the try_ functions can fail with, the other functions panic with the same message
+#[derive(Debug)]
+pub enum Error {
+    NotInitialized,
+    Display(glium::backend::glutin::DisplayCreationError),
+    Icon(String, glium::glutin::window::BadIcon),
+    Window(glium::glutin::error::ExternalError),
+    Shader(glium::ProgramCreationError),
+    VertexBuffer(glium::vertex::BufferCreationError),
+    IndexBuffer(glium::index::BufferCreationError),
+    InstancingNotSupported,
+    Draw(glium::DrawError),
+    SwapBuffers(glium::SwapBuffersError),
+    Triangulation(earcutr::Error),
+    Texture(TextureError),
+    Capture(CaptureError),
+    Font(FontError)
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotInitialized => write!(f, "blithaven is not initialized, call initialize or run first"),
+            Error::Display(error) => write!(f, "could not create the window: {}", error),
+            Error::Icon(path, error) => write!(f, "could not use '{}' as the window icon: {}", path, error),
+            Error::Window(error) => write!(f, "the window system refused: {}", error),
+            Error::Shader(error) => write!(f, "could not compile the shaders, the gpu may not support opengl 3.1: {}", error),
+            Error::VertexBuffer(error) => write!(f, "could not create a vertex buffer: {}", error),
+            Error::IndexBuffer(error) => write!(f, "could not create an index buffer: {:?}", error),
+            Error::InstancingNotSupported => write!(f, "instanced drawing is not supported by the gpu"),
+            Error::Draw(error) => write!(f, "could not draw: {}", error),
+            Error::SwapBuffers(error) => write!(f, "could not present the frame: {}", error),
+            Error::Triangulation(error) => write!(f, "could not triangulate the polygon: {}", error),
+            Error::Texture(error) => write!(f, "{}", error),
+            Error::Capture(error) => write!(f, "{}", error),
+            Error::Font(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NotInitialized => None,
+            Error::Display(error) => Some(error),
+            Error::Icon(_, error) => Some(error),
+            Error::Window(error) => Some(error),
+            Error::Shader(error) => Some(error),
+            Error::VertexBuffer(error) => Some(error),
+            Error::IndexBuffer(_) => None,
+            Error::InstancingNotSupported => None,
+            Error::Draw(error) => Some(error),
+            Error::SwapBuffers(error) => Some(error),
+            Error::Triangulation(_) => None,
+            Error::Texture(error) => Some(error),
+            Error::Capture(error) => Some(error),
+            Error::Font(error) => Some(error)
+        }
+    }
+}
+
+impl From<glium::backend::glutin::DisplayCreationError> for Error {
+    fn from(error: glium::backend::glutin::DisplayCreationError) -> Self {
+        Error::Display(error)
+    }
+}
+
+impl From<glium::glutin::error::ExternalError> for Error {
+    fn from(error: glium::glutin::error::ExternalError) -> Self {
+        Error::Window(error)
+    }
+}
+
+impl From<glium::ProgramCreationError> for Error {
+    fn from(error: glium::ProgramCreationError) -> Self {
+        Error::Shader(error)
+    }
+}
+
+impl From<glium::vertex::BufferCreationError> for Error {
+    fn from(error: glium::vertex::BufferCreationError) -> Self {
+        Error::VertexBuffer(error)
+    }
+}
+
+impl From<glium::index::BufferCreationError> for Error {
+    fn from(error: glium::index::BufferCreationError) -> Self {
+        Error::IndexBuffer(error)
+    }
+}
+
+impl From<glium::DrawError> for Error {
+    fn from(error: glium::DrawError) -> Self {
+        Error::Draw(error)
+    }
+}
+
+impl From<glium::SwapBuffersError> for Error {
+    fn from(error: glium::SwapBuffersError) -> Self {
+        Error::SwapBuffers(error)
+    }
+}
+
+impl From<earcutr::Error> for Error {
+    fn from(error: earcutr::Error) -> Self {
+        Error::Triangulation(error)
+    }
+}
+
+impl From<TextureError> for Error {
+    fn from(error: TextureError) -> Self {
+        Error::Texture(error)
+    }
+}
+
+impl From<CaptureError> for Error {
+    fn from(error: CaptureError) -> Self {
+        Error::Capture(error)
+    }
+}
+
+impl From<FontError> for Error {
+    fn from(error: FontError) -> Self {
+        Error::Font(error)
+    }
+}
@@ -0,0 +1,36 @@
+use std::{collections::HashMap, time::{Duration, Instant, SystemTime}};
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// remembers the modification time of every file backed texture so changes on disk can be picked up
+pub(crate) struct HotReload {
+    modified: HashMap<String, SystemTime>,
+    last_check: Instant
+}
+
+impl HotReload {
+    pub fn new() -> Self {
+        Self { modified: HashMap::new(), last_check: Instant::now() }
+    }
+
+    // the paths whose files changed since they were last seen, checked at most every CHECK_INTERVAL
+    pub fn changed_paths(&mut self, paths: Vec<String>) -> Vec<String> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return Vec::new()
+        }
+        self.last_check = Instant::now();
+
+        let mut changed = Vec::new();
+        for path in paths {
+            let modified = match std::fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue
+            };
+            match self.modified.insert(path.clone(), modified) {
+                Some(previous) if previous != modified => changed.push(path),
+                _ => ()
+            }
+        }
+        changed
+    }
+}
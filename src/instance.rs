@@ -0,0 +1,226 @@
+use std::rc::Rc;
+use glium::*;
+use crate::{stats, texture::Texture, BatchMap, Error, TextureHandle};
+
+// per-instance data streamed to the gpu every frame, the quad itself is only uploaded once
+#[derive(Clone, Copy)]
+pub(crate) struct Instance {
+    pub i_position: [f32; 2],
+    pub i_size: [f32; 2],
+    pub i_rotation: f32,
+    pub i_color: [f32; 4],
+    pub i_uv_rect: [f32; 4],
+    pub i_style: i32
+}
+
+implement_vertex!(Instance, i_position, i_size, i_rotation, i_color, i_uv_rect, i_style);
+
+pub(crate) const INSTANCE_STYLE_RECT: i32 = 0;
+pub(crate) const INSTANCE_STYLE_CIRCLE: i32 = 1;
+pub(crate) const INSTANCE_STYLE_SPRITE: i32 = 3;
+
+#[derive(Clone, Copy)]
+struct Corner {
+    corner: [f32; 2]
+}
+
+implement_vertex!(Corner, corner);
+
+pub(crate) struct InstanceBatch {
+    instances: Vec<Instance>,
+    quad: Rc<VertexBuffer<Corner>>,
+    indices: Rc<IndexBuffer<u16>>,
+    // created on the first draw, so batches sharing the quad can be made without touching the gpu
+    instance_buffer: Option<VertexBuffer<Instance>>,
+    program: Rc<Program>,
+    pub texture: Option<Rc<Texture>>
+}
+
+impl InstanceBatch {
+    pub fn new(display: &Display, texture: Option<Rc<Texture>>) -> Result<Self, Error> {
+        let vertex_shader = String::from("
+            #version 140
+
+            in vec2 corner;
+            in vec2 i_position;
+            in vec2 i_size;
+            in float i_rotation;
+            in vec4 i_color;
+            in vec4 i_uv_rect;
+            in int i_style;
+
+            out vec4 v_color;
+            out vec2 v_corner;
+            out vec2 v_tex_coord;
+            flat out int v_style;
+
+            uniform mat4 matrix;
+            uniform vec2 window_size;
+
+            void main() {
+                vec2 offset = (corner - 0.5) * i_size;
+                float c = cos(i_rotation);
+                float s = sin(i_rotation);
+                vec2 pixel = i_position + 0.5 * i_size + vec2(offset.x * c - offset.y * s, offset.x * s + offset.y * c);
+                vec2 position = vec2(pixel.x, -pixel.y) / (window_size / 2.0);
+
+                v_color = i_color;
+                v_corner = corner;
+                v_tex_coord = vec2(i_uv_rect.x + corner.x * i_uv_rect.z, 1.0 - (i_uv_rect.y + corner.y * i_uv_rect.w));
+                v_style = i_style;
+                gl_Position = matrix * vec4(position, 0.0, 1.0);
+            }
+        ");
+
+        let fragment_shader = String::from("
+            #version 140
+
+            in vec4 v_color;
+            in vec2 v_corner;
+            in vec2 v_tex_coord;
+            flat in int v_style;
+
+            out vec4 f_color;
+
+            uniform sampler2D tex;
+
+            void main() {
+              if (v_style == 3) {
+                f_color = texture(tex, v_tex_coord) * v_color;
+              }
+              else if (v_style == 1) {
+                if (distance(v_corner, vec2(0.5)) > 0.5) {
+                  discard;
+                }
+                f_color = v_color;
+              }
+              else {
+                f_color = v_color;
+              }
+            }
+        ");
+
+        let program = Program::from_source(display, &vertex_shader, &fragment_shader, None)?;
+
+        let quad = VertexBuffer::new(display, &[
+            Corner { corner: [0.0, 0.0] },
+            Corner { corner: [1.0, 0.0] },
+            Corner { corner: [1.0, 1.0] },
+            Corner { corner: [0.0, 1.0] }
+        ])?;
+        let indices = IndexBuffer::new(display, index::PrimitiveType::TrianglesList, &[0u16, 1, 2, 0, 3, 2])?;
+
+        Ok(Self {
+            instances: Vec::new(),
+            quad: Rc::new(quad),
+            indices: Rc::new(indices),
+            instance_buffer: None,
+            program: Rc::new(program),
+            texture
+        })
+    }
+
+    // a batch for another texture that reuses this one's program and quad
+    pub fn share(&self, texture: Option<Rc<Texture>>) -> Self {
+        Self {
+            instances: Vec::new(),
+            quad: self.quad.clone(),
+            indices: self.indices.clone(),
+            instance_buffer: None,
+            program: self.program.clone(),
+            texture
+        }
+    }
+
+    pub fn push(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+
+    // window_size is in logical pixels, the same space the instances are positioned in
+    pub fn draw(&mut self, frame: &mut Frame, display: &Display, window_size: [f32; 2], draw_params: &DrawParameters) -> Result<(), Error> {
+        if self.instances.is_empty() {
+            return Ok(())
+        }
+        let instance_list = std::mem::take(&mut self.instances);
+
+        // grow the streaming buffer in powers of two so big particle counts don't reallocate every frame
+        let instance_buffer = match self.instance_buffer.take() {
+            Some(instance_buffer) if instance_buffer.len() >= instance_list.len() => instance_buffer,
+            _ => VertexBuffer::empty_dynamic(display, instance_list.len().next_power_of_two().max(1024))?
+        };
+        let instance_buffer = self.instance_buffer.insert(instance_buffer);
+        instance_buffer.invalidate();
+        let instances = instance_buffer.slice(0 .. instance_list.len()).unwrap();
+        instances.write(&instance_list);
+
+        let matrix = [
+            [1.0, 0.0, 0.5, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [-1.0, 1.0, -1.0, 1.0f32],
+        ];
+        let per_instance = instances.per_instance().map_err(|_| Error::InstancingNotSupported)?;
+        stats::count_batch();
+        stats::count_draw(self.quad.len() * instance_list.len(), self.texture.is_some());
+
+        match &self.texture {
+            Some(texture) => {
+                let uniforms = glium::uniform! {
+                    matrix: matrix,
+                    window_size: window_size,
+                    tex: glium::uniforms::Sampler(&texture.texture, texture.sampler_behavior())
+                };
+                frame.draw((&*self.quad, per_instance), &*self.indices, &self.program, &uniforms, draw_params)?;
+            },
+            None => {
+                let uniforms = glium::uniform! {
+                    matrix: matrix,
+                    window_size: window_size
+                };
+                frame.draw((&*self.quad, per_instance), &*self.indices, &self.program, &uniforms, draw_params)?;
+            }
+        }
+
+        // hand the vec back so its capacity is reused next frame
+        self.instances = instance_list;
+        self.instances.clear();
+        Ok(())
+    }
+}
+
+// the instanced rects and circles of one layer, and a batch per texture for its instanced sprites
+pub(crate) struct Instances {
+    pub shapes: InstanceBatch,
+    pub sprites: BatchMap<TextureHandle, InstanceBatch>
+}
+
+impl Instances {
+    pub fn new(shapes: InstanceBatch) -> Self {
+        Self { shapes, sprites: BatchMap::new() }
+    }
+
+    pub fn sprite_batch(&mut self, handle: TextureHandle, texture: Option<Rc<Texture>>) -> &mut InstanceBatch {
+        let shapes = &self.shapes;
+        self.sprites.get_or_insert_with(handle, || shapes.share(texture))
+    }
+
+    pub fn replace_texture(&mut self, old: &Rc<Texture>, texture: &Rc<Texture>) {
+        for instance_batch in self.sprites.iter_mut() {
+            if instance_batch.texture.as_ref().is_some_and(|instance_texture| Rc::ptr_eq(instance_texture, old)) {
+                instance_batch.texture = Some(texture.clone());
+            }
+        }
+    }
+
+    pub fn remove_texture(&mut self, handle: TextureHandle) {
+        self.sprites.retain(|key| *key != handle);
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, display: &Display, window_size: [f32; 2], draw_params: &DrawParameters) -> Result<(), Error> {
+        self.shapes.draw(frame, display, window_size, draw_params)?;
+        for instance_batch in self.sprites.iter_mut() {
+            instance_batch.draw(frame, display, window_size, draw_params)?;
+        }
+        Ok(())
+    }
+}
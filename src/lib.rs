@@ -1,31 +1,76 @@
-use std::{time::{Instant, Duration, self}, fs::File, io::Read};
+use std::{time::{Instant, Duration, self}, rc::Rc};
 pub use glium::glutin::event_loop::EventLoop;
 pub use glium::glutin::event::VirtualKeyCode;
 pub use glium::glutin::event::MouseButton;
-use glium::{*, glutin::ContextBuilder};
+pub use glium::glutin::window::CursorIcon;
+use glium::*;
+use glium::draw_parameters::TimeElapsedQuery;
 use glutin::event::*;
-use glutin::window::*;
+
+mod instance;
+use instance::*;
+mod sprite_sheet;
+pub use sprite_sheet::SpriteSheet;
+mod texture;
+use texture::Texture;
+mod dynamic_texture;
+pub use dynamic_texture::DynamicTexture;
+mod pixel_buffer;
+use pixel_buffer::PixelBuffer;
+mod capture;
+pub use capture::CaptureError;
+use capture::PendingCapture;
+mod recording;
+use recording::Recorder;
+mod hot_reload;
+use hot_reload::HotReload;
+mod assets;
+use assets::{Assets, BatchMap};
+mod error;
+pub use error::Error;
+mod loader;
+use loader::Loader;
+pub use loader::LoadingProgress;
+mod window_config;
+pub use window_config::{WindowConfig, WindowMode};
+mod window;
+mod timing;
+use timing::{FixedUpdate, FrameClock};
+mod stats;
+use stats::StatsCollector;
+pub use stats::FrameStats;
+mod profiler;
+use profiler::Profiler;
+pub use profiler::PassTiming;
+mod font;
+use font::BitmapFont;
+mod truetype;
+use truetype::TrueTypeFont;
+pub use truetype::{FontHandle, FontError};
+mod text_layout;
+use text_layout::{GlyphQuad, TextBlock};
+pub use text_layout::{TextAlign, TextAnchor, TextOptions};
+use window::{CursorImage, WindowState};
+pub use window::WindowHandle;
+pub use assets::{TextureHandle, TextureKey};
+pub use recording::{RecordingOptions, RecordingFormat};
+pub use texture::{TextureOptions, Filter, Wrap, TextureError};
+pub use image::{DynamicImage, RgbaImage};
 
 enum Action {
     Continue
 }
 
-const A: [u8; 64] = 
-[
-  0,0,1,1,0,0,0,0,
-  0,1,1,1,1,0,0,0,
-  1,1,0,0,1,1,0,0,
-  1,1,0,0,1,1,0,0,
-  1,1,1,1,1,1,0,0,
-  1,1,0,0,1,1,0,0,
-  1,1,0,0,1,1,0,0,
-  0,0,0,0,0,0,0,0
-];
-
-pub fn start_loop<F>(event_loop: EventLoop<()>, mut input_code: F)->! where F: 'static + FnMut(&Vec<Event<'_, ()>>) {
+pub fn start_loop<F>(event_loop: EventLoop<()>, mut input_code: F)->! where F: 'static + FnMut(&Vec<Event<'static, ()>>) {
     let mut events_buffer = Vec::new();
     let mut next_frame_time = Instant::now();
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, window_target, control_flow| {
+        // this event borrows the new size, so to_static drops it. the app has to hear about it here
+        if let Event::WindowEvent { window_id, event: WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } } = &event {
+            if let Ok(app) = try_get_app() {
+                app.scale_factor_changed(*window_id, *scale_factor, **new_inner_size);
+            }
+        }
         let run_callback = match event.to_static() {
             Some(Event::NewEvents(cause)) => {
                 match cause {
@@ -46,13 +91,29 @@ pub fn start_loop<F>(event_loop: EventLoop<()>, mut input_code: F)->! where F: '
         };
 
         let action = if run_callback {
+            // runs the fixed updates that are due before the frame is drawn, and lets the windows see
+            // their events during it
+            if let Ok(app) = try_get_app() {
+                app.route_events(&events_buffer);
+                app.begin_frame();
+            }
             input_code(&events_buffer);
-            next_frame_time = Instant::now() + Duration::from_nanos(1667);
-            // TODO: Add back the old accumulator loop in some way
+            // windows opened during the frame need the event loop, which only exists in here
+            if let Ok(app) = try_get_app() {
+                app.open_pending_windows(window_target);
+            }
+            // counted from when this frame was due rather than when it ended, so the frame rate doesn't
+            // drift. a frame that ran late is followed by the next one right away
+            let frame_time = try_get_app().map_or(timing::frame_time(timing::DEFAULT_TARGET_FPS), |app| app.clock.target_frame_time);
+            next_frame_time = match frame_time {
+                Some(frame_time) => (next_frame_time + frame_time).max(Instant::now()),
+                None => Instant::now()
+            };
             for event in events_buffer.iter() {
                 match event {
-                    glutin::event::Event::WindowEvent { event, .. } => match event {
-                        glutin::event::WindowEvent::CloseRequested => {
+                    // closing any other window only closes that window, see App::finish
+                    glutin::event::Event::WindowEvent { event, window_id } => match event {
+                        glutin::event::WindowEvent::CloseRequested if try_get_app().map_or(true, |app| app.is_main_window(*window_id)) => {
                             *control_flow = glutin::event_loop::ControlFlow::Exit;
                             return
                         },
@@ -78,25 +139,63 @@ pub fn start_loop<F>(event_loop: EventLoop<()>, mut input_code: F)->! where F: '
 
 
 pub struct App {
-    options: Options,
-    display: Display,
-    batch: Batch,
-    texture_batches: Vec<TextureBatch>,
+    // indexed by WindowHandle, a closed window leaves None behind so other handles stay valid
+    surfaces: Vec<Option<WindowState>>,
+    // the window draw calls go to, always an open one
+    target: usize,
+    pending_windows: Vec<(usize, WindowConfig)>,
+    programs: Programs,
+    assets: Assets,
     last_frame_time: Instant,
-    last_fps_output: Instant,
-    animations: Vec<Animation>
+    stats: StatsCollector,
+    stats_overlay: bool,
+    profiler: Profiler,
+    font: BitmapFont,
+    fonts: Vec<TrueTypeFont>,
+    text_font: Option<FontHandle>,
+    animations: Vec<Animation>,
+    pixel_buffer: Option<PixelBuffer>,
+    screenshot_key: Option<VirtualKeyCode>,
+    // what this frame's events asked for, acted on once it's drawn
+    closed_windows: Vec<WindowHandle>,
+    // start_loop routes the events before the frame closure, finish only does for loops of their own
+    events_routed: bool,
+    // the same for ticking the clock, finish ticks it when the loop didn't
+    frame_begun: bool,
+    recorder: Option<Recorder>,
+    hot_reload: Option<HotReload>,
+    loader: Loader,
+    scale_factor_override: Option<f64>,
+    clock: FrameClock
 }
 
 impl App {
     pub fn new(title: &str, window_width: u32, window_height: u32) -> (Self, EventLoop<()>) {
+        Self::with_config(WindowConfig::new(title, window_width, window_height))
+    }
+
+    // reports a missing display or a gpu without opengl 3.1 instead of panicking
+    pub fn try_new(title: &str, window_width: u32, window_height: u32) -> Result<(Self, EventLoop<()>), Error> {
+        Self::try_with_config(WindowConfig::new(title, window_width, window_height))
+    }
+
+    pub fn with_config(config: WindowConfig) -> (Self, EventLoop<()>) {
+        Self::try_with_config(config).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_with_config(config: WindowConfig) -> Result<(Self, EventLoop<()>), Error> {
         let event_loop = EventLoop::new();
-        let window_builder = WindowBuilder::new().with_title(title).with_inner_size(glutin::dpi::Size::from(glutin::dpi::LogicalSize::new(window_width, window_height))).with_resizable(true);
-        let context_buffer = ContextBuilder::new().with_depth_buffer(25);
+        let window_builder = config.window_builder(&event_loop)?;
+        let context_buffer = config.context_builder();
 
-        let display = Display::new(window_builder, context_buffer, &event_loop).unwrap();
-        let batch = Batch::new(&display, window_width as i32, window_height as i32);
+        let display = Display::new(window_builder, context_buffer, &event_loop)?;
+        let programs = Programs::new(&display)?;
+        let instance_batch = InstanceBatch::new(&display, None)?;
+        let mut assets = Assets::new();
+        let font = BitmapFont::new(&display, &mut assets)?;
+        let surface = WindowState::new(display, &config, &programs, instance_batch, None);
 
-        ( App { display, batch, options: Options::new(window_width as i32, window_height as i32), texture_batches: Vec::new(), last_frame_time: Instant::now(), last_fps_output: Instant::now(), animations: vec![] }, event_loop)
+        Ok(( App { surfaces: vec![Some(surface)], target: 0, pending_windows: Vec::new(), programs, assets, last_frame_time: Instant::now(), stats: StatsCollector::new(), stats_overlay: false, profiler: Profiler::new(), font, fonts: Vec::new(), text_font: None, animations: vec![], pixel_buffer: None, screenshot_key: None, closed_windows: Vec::new(), events_routed: false, frame_begun: false, recorder: None, hot_reload: None, loader: Loader::new(), scale_factor_override: None, clock: FrameClock::new() }, event_loop))
     }
     // pub fn new_with_loop(title: &str, window_width: u32, window_height: u32, event_loop: EventLoop<()>) -> Self {
     //     let window_builder = WindowBuilder::new().with_title(title).with_inner_size(glutin::dpi::Size::from(glutin::dpi::LogicalSize::new(window_width, window_height))).with_resizable(true);
@@ -108,78 +207,551 @@ impl App {
     //     App { display, batch, options: Options::new(window_width as i32, window_height as i32), texture_batches: Vec::new(), last_frame_time: Instant::now(), last_fps_output: Instant::now() }
     // }
 
+    // for the window draw calls currently go to
     pub fn use_pixel_coords(&mut self, param: bool) {
-        self.options.use_pixel_space = param;
+        self.surface_mut().options.use_pixel_space = param;
+    }
+
+    fn surface(&self) -> &WindowState {
+        self.surfaces[self.target].as_ref().unwrap()
+    }
+
+    fn surface_mut(&mut self) -> &mut WindowState {
+        self.surfaces[self.target].as_mut().unwrap()
+    }
+
+    fn open_surface(&self, window: WindowHandle) -> Option<&WindowState> {
+        self.surfaces.get(window.0)?.as_ref()
+    }
+
+    // textures are uploaded through the main window's context, the other windows share its objects
+    fn display(&self) -> &Display {
+        &self.surfaces[0].as_ref().unwrap().display
+    }
+
+    fn surface_index(&self, window_id: glutin::window::WindowId) -> Option<usize> {
+        self.surfaces.iter().position(|surface| surface.as_ref().is_some_and(|surface| surface.id == window_id))
+    }
+
+    pub(crate) fn is_main_window(&self, window_id: glutin::window::WindowId) -> bool {
+        self.surface_index(window_id) == Some(0)
+    }
+
+    // the window opens before the next frame, it shares textures with every other window. closing the
+    // main window still ends the app, closing this one only closes it
+    pub fn open_window(&mut self, config: WindowConfig) -> WindowHandle {
+        self.surfaces.push(None);
+        let index = self.surfaces.len() - 1;
+        self.pending_windows.push((index, config));
+        WindowHandle(index)
+    }
+
+    pub(crate) fn open_pending_windows<T>(&mut self, window_target: &glutin::event_loop::EventLoopWindowTarget<T>) {
+        for (index, config) in std::mem::take(&mut self.pending_windows) {
+            match self.try_open_surface(&config, window_target) {
+                Ok(surface) => {
+                    log::info!("opened window {}", index);
+                    self.surfaces[index] = Some(surface);
+                },
+                Err(error) => log::error!("could not open window {}: {}", index, error)
+            }
+        }
+    }
+
+    fn try_open_surface<T>(&self, config: &WindowConfig, window_target: &glutin::event_loop::EventLoopWindowTarget<T>) -> Result<WindowState, Error> {
+        let main = self.surfaces[0].as_ref().unwrap();
+        let gl_window = main.display.gl_window();
+        // sharing lists with the main context is what lets this window use its textures, programs and buffers
+        let context_builder = config.context_builder().with_shared_lists(gl_window.context());
+        let display = Display::new(config.window_builder(window_target)?, context_builder, window_target)?;
+        Ok(WindowState::new(display, config, &self.programs, main.base_layers[0].instances.shapes.share(None), self.scale_factor_override))
+    }
+
+    // the main window can't be closed on its own, it closes with the app
+    pub fn close_window(&mut self, window: WindowHandle) {
+        if window == WindowHandle::MAIN || window.0 >= self.surfaces.len() {
+            return
+        }
+        self.surfaces[window.0] = None;
+        self.pending_windows.retain(|(index, _)| *index != window.0);
+        if self.target == window.0 {
+            self.target = 0;
+        }
+    }
+
+    // false until the window has actually opened, and after it's closed
+    pub fn is_window_open(&self, window: WindowHandle) -> bool {
+        self.open_surface(window).is_some()
+    }
+
+    // draw calls and the window functions go to this window until the frame is finished, then back to
+    // the main window. returns false and changes nothing for a window that isn't open
+    pub fn set_target_window(&mut self, window: WindowHandle) -> bool {
+        if !self.is_window_open(window) {
+            return false
+        }
+        self.target = window.0;
+        true
+    }
+
+    pub fn target_window(&self) -> WindowHandle {
+        WindowHandle(self.target)
+    }
+
+    // the events that arrived for this window since the last frame
+    pub fn window_events(&self, window: WindowHandle) -> &[WindowEvent<'static>] {
+        self.open_surface(window).map_or(&[], |surface| &surface.events)
+    }
+
+    // in logical pixels, None for a window that isn't open
+    pub fn window_dims(&self, window: WindowHandle) -> Option<[u32; 2]> {
+        let surface = self.open_surface(window)?;
+        Some([surface.options.window_width as u32, surface.options.window_height as u32])
+    }
+
+    // the last position the mouse had over this window, in logical pixels
+    pub fn window_mouse_pos(&self, window: WindowHandle) -> Option<[f32; 2]> {
+        Some(self.open_surface(window)?.mouse_position(self.scale_factor_override))
+    }
+
+    pub fn window_key_down(&self, window: WindowHandle, key: VirtualKeyCode) -> bool {
+        self.open_surface(window).is_some_and(|surface| surface.pressed_keys.contains(&key))
+    }
+
+    pub fn window_mouse_down(&self, window: WindowHandle, button: MouseButton) -> bool {
+        self.open_surface(window).is_some_and(|surface| surface.pressed_buttons.contains(&button))
     }
 
+    pub fn window_resized(&self, window: WindowHandle) -> bool {
+        self.window_events(window).iter().any(|event| matches!(event, WindowEvent::Resized(_)))
+    }
 
-    pub fn finish(&mut self, clear_color: [f32; 3], events: &Vec<Event<()>>) {
+    // hands the events to the windows they're for, so window_events and window_key_down see them during
+    // the frame closure
+    pub(crate) fn route_events(&mut self, events: &[Event<'static, ()>]) {
+        self.events_routed = true;
+        for surface in self.surfaces.iter_mut().flatten() {
+            surface.events.clear();
+        }
         for event in events.iter() {
+            let (window_id, event) = match event {
+                Event::WindowEvent { window_id, event } => (*window_id, event),
+                _ => continue
+            };
+            let index = match self.surface_index(window_id) {
+                Some(index) => index,
+                None => continue
+            };
             match event {
-                glutin::event::Event::WindowEvent { event, .. } => match event {
-                        glutin::event::WindowEvent::Resized(u) => {
-                            self.batch.window_height = u.height as i32;
-                            self.batch.window_width = u.width as i32;
-                            self.options.window_height = u.height as i32;
-                            self.options.window_width = u.width as i32;
-                        }
-                    _ => (),
-                },
-                _ => (),
+                glutin::event::WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed && input.virtual_keycode.is_some() && input.virtual_keycode == self.screenshot_key => {
+                    let path = capture::timestamped_path("screenshot", "png");
+                    self.surfaces[index].as_mut().unwrap().captures.push(PendingCapture::Save(path));
+                }
+                glutin::event::WindowEvent::CloseRequested if index != 0 => {
+                    self.closed_windows.push(WindowHandle(index));
+                }
+                _ => ()
             }
-        };
+            let scale_factor_override = self.scale_factor_override;
+            self.surfaces[index].as_mut().unwrap().handle_event(event, scale_factor_override);
+        }
+    }
 
-        let mut frame = self.display.draw();
-        frame.clear_color_and_depth((clear_color[0], clear_color[1], clear_color[2], 1.0), 1.0);
-        self.batch.draw(&mut frame, &self.display);
-        for texture_batch in self.texture_batches.iter_mut() {
-            texture_batch.draw(&mut frame, &self.display);
+    pub fn finish(&mut self, clear_color: [f32; 3], events: &Vec<Event<'static, ()>>) {
+        self.try_finish(clear_color, events).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_finish(&mut self, clear_color: [f32; 3], events: &Vec<Event<'static, ()>>) -> Result<(), Error> {
+        if !self.events_routed {
+            self.route_events(events);
+        }
+        self.events_routed = false;
+        // apps calling finish themselves would otherwise never get a delta time or fixed updates
+        if !self.frame_begun {
+            self.begin_frame();
+        }
+        self.frame_begun = false;
+        let closed_windows = std::mem::take(&mut self.closed_windows);
+
+        self.reload_changed_textures();
+        self.upload_loaded_textures();
+        for font in self.fonts.iter_mut() {
+            font.upload();
+        }
+
+        if self.stats_overlay {
+            self.draw_stats_overlay();
+        }
+
+        // frames are copied before they're finished, so what gets captured is decided before drawing
+        let record = self.recorder.as_mut().is_some_and(|recorder| recorder.wants_frame());
+        for surface in self.surfaces.iter_mut().flatten() {
+            surface.capture_frame = surface.captures.is_pending();
         }
-        frame.finish().unwrap();
 
-        if self.last_fps_output.elapsed() > Duration::from_secs(1) {
-            println!("{:?}", 1.0 / (self.last_frame_time.elapsed().as_secs_f64()));
-            self.last_fps_output = Instant::now();
+        // every window is drawn even if one of them fails, so none keeps this frame's quads for the next one
+        let mut drawn = Ok(());
+        for index in 0 .. self.surfaces.len() {
+            drawn = drawn.and(self.draw_surface(index, clear_color, index == 0 && record));
         }
+        self.target = 0;
+        for font in self.fonts.iter_mut() {
+            font.end_frame();
+        }
+        self.profiler.end_frame();
+        self.stats.end_frame(self.last_frame_time.elapsed(), stats::take_draw_counts(), self.profiler.latest());
         self.last_frame_time = Instant::now();
+        drawn?;
+
+        for surface in self.surfaces.iter_mut().flatten() {
+            let last_frame = surface.last_frame.as_ref();
+            surface.captures.deliver(|| capture::read_frame(last_frame.ok_or(CaptureError::NoFrame)?));
+        }
+        for window in closed_windows {
+            self.close_window(window);
+        }
+        Ok(())
+    }
+
+    // what the last finished frame cost, across all windows
+    pub fn frame_stats(&self) -> FrameStats {
+        self.stats.stats()
+    }
+
+    // draws a frame time graph and the frame stats in the top left corner of the main window
+    pub fn set_stats_overlay(&mut self, enabled: bool) {
+        self.stats_overlay = enabled;
+    }
+
+    pub fn toggle_stats_overlay(&mut self) {
+        self.stats_overlay = !self.stats_overlay;
+    }
+
+    // times every pass of the frame on the cpu and, where timer queries are supported, on the gpu.
+    // the timings show up in frame_stats().passes
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.set_enabled(enabled);
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.is_enabled()
+    }
+
+    // records pass timings until stop_trace, turns profiling on
+    pub fn start_trace(&mut self) {
+        self.profiler.set_enabled(true);
+        self.profiler.start_trace();
+    }
+
+    // writes the recorded timings as a chrome trace (chrome://tracing, perfetto) and returns how many
+    // events it wrote. frames whose gpu timings hadn't arrived yet are left out
+    pub fn stop_trace(&mut self, path: &str) -> Result<usize, CaptureError> {
+        self.profiler.stop_trace(path)
+    }
+
+    fn draw_stats_overlay(&mut self) {
+        const GRAPH_HEIGHT: i32 = 40;
+        // a frame this long fills the graph, the line in it marks 60 fps
+        const GRAPH_MAX_MS: f32 = 1000.0 / 30.0;
+        const TEXT_SIZE: i32 = 8;
+        let stats = self.stats.stats();
+        let lines = [
+            format!("fps {:.0}", stats.fps),
+            format!("ms {:.1} {:.1} {:.1}", stats.frame_time_min, stats.frame_time_avg, stats.frame_time_max),
+            format!("draws {}", stats.draw_calls),
+            format!("verts {}", stats.vertices),
+            format!("binds {}", stats.texture_binds),
+            format!("batches {}", stats.batches)
+        ];
+
+        // always in pixels on the main window, whatever the frame was set up for
+        self.target = 0;
+        let surface = self.surface_mut();
+        let use_pixel_space = std::mem::replace(&mut surface.options.use_pixel_space, true);
+        surface.draw_target = DrawTarget::Base;
+
+        let [x, y] = [8, 8];
+        let width = stats::FRAME_HISTORY as i32 + 8;
+        let height = GRAPH_HEIGHT + 12 + lines.len() as i32 * (TEXT_SIZE + 2);
+        self.rect([x, y], width, height, (0.05, 0.05, 0.05));
+        let graph_bottom = y + 4 + GRAPH_HEIGHT;
+        for (i, frame_time) in self.stats.frame_times().collect::<Vec<_>>().into_iter().enumerate() {
+            let milliseconds = frame_time.as_secs_f32() * 1000.0;
+            let bar_height = ((milliseconds / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT as f32).max(1.0) as i32;
+            let color = match milliseconds {
+                ms if ms <= 1000.0 / 55.0 => (0.2, 0.8, 0.3),
+                ms if ms <= 1000.0 / 30.0 => (0.9, 0.8, 0.2),
+                _ => (0.9, 0.25, 0.2)
+            };
+            self.rect([x + 4 + i as i32, graph_bottom - bar_height], 1, bar_height, color);
+        }
+        self.rect([x + 4, graph_bottom - GRAPH_HEIGHT / 2], stats::FRAME_HISTORY as i32, 1, (0.5, 0.5, 0.5));
+        for (i, line) in lines.iter().enumerate() {
+            self.bitmap_text(line, [x + 4, graph_bottom + 6 + i as i32 * (TEXT_SIZE + 2)], TEXT_SIZE as f32);
+        }
+
+        self.surface_mut().options.use_pixel_space = use_pixel_space;
+    }
+
+    fn draw_surface(&mut self, index: usize, clear_color: [f32; 3], record: bool) -> Result<(), Error> {
+        let surface = match self.surfaces[index].as_mut() {
+            Some(surface) => surface,
+            None => return Ok(())
+        };
+        let mut frame = surface.display.draw();
+        let alpha = if surface.transparent { 0.0 } else { 1.0 };
+        frame.clear_color_and_depth((clear_color[0], clear_color[1], clear_color[2], alpha), 1.0);
+        // the frame has to be finished even if drawing failed, glium panics when it's dropped unfinished.
+        // the pixel buffer is only stretched over the main window
+        self.profiler.set_window(WindowHandle(index));
+        let drawn = match (index, self.pixel_buffer.as_mut()) {
+            (0, Some(pixel_buffer)) => self.profiler.pass(&surface.display, || "pixel buffer".to_string(), |query| pixel_buffer.draw(&mut frame, &surface.display, query)),
+            _ => Ok(())
+        };
+        let drawn = drawn.and_then(|_| surface.draw_batches(&mut frame, &self.assets, self.scale_factor_override, &mut self.profiler));
+        if surface.capture_frame {
+            if let Err(error) = capture::copy_frame(&surface.display, &frame, &mut surface.last_frame, frame.get_dimensions()) {
+                log::error!("{}", error);
+                surface.last_frame = None;
+            }
+        }
+        if let Some(recorder) = self.recorder.as_mut().filter(|_| record) {
+            recorder.capture(&surface.display, &frame);
+        }
+        surface.used_mask_layers = 0;
+        surface.draw_target = DrawTarget::Base;
+        let finished = frame.finish();
+        drawn?;
+        finished?;
+        Ok(())
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame_begun = true;
+        let steps = self.clock.tick();
+        // taken out while it runs, so the callback can draw and use the app like the frame closure does
+        let (step, mut fixed_update) = match (self.clock.fixed_step(), self.clock.fixed_update.take()) {
+            (Some(step), Some(fixed_update)) => (step, fixed_update),
+            _ => return
+        };
+        let generation = self.clock.fixed_generation;
+        for _ in 0 .. steps {
+            fixed_update(step.as_secs_f32());
+            // set_fixed_update or clear_fixed_update was called by the callback, the rest of its steps are dropped
+            if self.clock.fixed_generation != generation {
+                return
+            }
+        }
+        self.clock.fixed_update = Some(fixed_update);
+    }
+
+    // frames per second the loop aims for, 0 draws them as fast as it can (or as vsync allows). the
+    // default is 60
+    pub fn set_target_fps(&mut self, fps: u32) {
+        self.clock.target_frame_time = timing::frame_time(fps);
+    }
+
+    // seconds between the start of the previous frame and this one
+    pub fn delta_time(&self) -> f32 {
+        self.clock.delta().as_secs_f32()
+    }
+
+    // seconds from opening the app to the start of this frame
+    pub fn elapsed(&self) -> f32 {
+        self.clock.elapsed().as_secs_f32()
+    }
+
+    // calls update steps_per_second times a second of real time with the step length in seconds, before
+    // the frame it catches up in. the frame itself can smooth over what's left with interpolation_alpha
+    pub fn set_fixed_update<F>(&mut self, steps_per_second: u32, update: F) where F: 'static + FnMut(f32) {
+        let step = Duration::from_secs(1) / steps_per_second.max(1);
+        self.clock.set_fixed_update(Some(FixedUpdate { step, callback: Box::new(update) }));
+    }
+
+    pub fn clear_fixed_update(&mut self) {
+        self.clock.set_fixed_update(None);
+    }
+
+    // how far this frame is from the last fixed step towards the next, 0.0 to 1.0. draw at
+    // previous + (current - previous) * alpha to hide the steps
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.clock.alpha()
+    }
+
+    // the ratio of physical to logical pixels of the target window, every position and size the api
+    // takes is in logical pixels
+    pub fn scale_factor(&self) -> f64 {
+        self.surface().scale_factor(self.scale_factor_override)
+    }
+
+    // pretends every display has this scale factor, to check a layout for hidpi screens on a normal one
+    pub fn set_scale_factor_override(&mut self, scale_factor: Option<f64>) {
+        self.scale_factor_override = scale_factor;
+        for surface in self.surfaces.iter_mut().flatten() {
+            surface.resize(surface.physical_size, scale_factor);
+        }
+    }
+
+    pub(crate) fn scale_factor_changed(&mut self, window_id: glutin::window::WindowId, scale_factor: f64, physical_size: glutin::dpi::PhysicalSize<u32>) {
+        let scale_factor_override = self.scale_factor_override;
+        if let Some(surface) = self.surfaces.iter_mut().flatten().find(|surface| surface.id == window_id) {
+            surface.scale_factor = scale_factor;
+            surface.resize([physical_size.width, physical_size.height], scale_factor_override);
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.surface().display.gl_window().window().set_title(title);
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        self.surface().window_mode
+    }
+
+    pub fn set_window_mode(&mut self, window_mode: WindowMode) {
+        let surface = self.surface_mut();
+        let gl_window = surface.display.gl_window();
+        let window = gl_window.window();
+        window.set_fullscreen(window_config::fullscreen(window_mode, window.current_monitor()));
+        surface.window_mode = window_mode;
+    }
+
+    // switches between windowed and borderless fullscreen, exclusive fullscreen goes back to windowed
+    pub fn toggle_fullscreen(&mut self) {
+        match self.window_mode() {
+            WindowMode::Windowed => self.set_window_mode(WindowMode::Borderless),
+            _ => self.set_window_mode(WindowMode::Windowed)
+        }
+    }
+
+    // the new size arrives as a resize event, get_dims changes on the frame after
+    pub fn set_window_size(&mut self, width: u32, height: u32) {
+        self.surface().display.gl_window().window().set_inner_size(glutin::dpi::LogicalSize::new(width, height));
+    }
+
+    pub fn set_window_position(&mut self, x: i32, y: i32) {
+        self.surface().display.gl_window().window().set_outer_position(glutin::dpi::LogicalPosition::new(x, y));
+    }
+
+    // None on platforms that don't tell, like wayland
+    pub fn window_position(&self) -> Option<[i32; 2]> {
+        let position = self.surface().display.gl_window().window().outer_position().ok()?.to_logical::<i32>(self.scale_factor());
+        Some([position.x, position.y])
+    }
+
+    pub fn set_minimized(&mut self, minimized: bool) {
+        let surface = self.surface_mut();
+        surface.display.gl_window().window().set_minimized(minimized);
+        surface.minimized = minimized;
+    }
+
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.surface().display.gl_window().window().set_maximized(maximized);
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.surface().display.gl_window().window().is_maximized()
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.surface().minimized
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.surface().focused
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.surface().display.gl_window().window().set_cursor_visible(visible);
+    }
+
+    // keeps the cursor inside the window, or locks it in place where confining isn't supported
+    pub fn set_cursor_grab(&mut self, grab: bool) -> Result<(), Error> {
+        let gl_window = self.surface().display.gl_window();
+        let window = gl_window.window();
+        if !grab {
+            return Ok(window.set_cursor_grab(glutin::window::CursorGrabMode::None)?)
+        }
+        window.set_cursor_grab(glutin::window::CursorGrabMode::Confined)
+            .or_else(|_| window.set_cursor_grab(glutin::window::CursorGrabMode::Locked))?;
+        Ok(())
+    }
+
+    pub fn set_cursor_icon(&mut self, cursor_icon: CursorIcon) {
+        self.surface().display.gl_window().window().set_cursor_icon(cursor_icon);
+    }
+
+    // hides the os cursor and draws the texture in its place, on top of everything else. hotspot is
+    // the pixel of the image that points at the mouse position
+    pub fn set_cursor_image<'a>(&mut self, texture: impl Into<TextureKey<'a>>, width: i32, height: i32, hotspot: [i32; 2]) -> Result<(), Error> {
+        let handle = match self.resolve_texture(texture.into())? {
+            Some(handle) => handle,
+            None => return Ok(())
+        };
+        let surface = self.surfaces[self.target].as_mut().unwrap();
+        let batch = TextureBatch::with_texture(surface.options.window_width, surface.options.window_height, self.programs.texture.clone(), self.assets.get(handle).unwrap());
+        surface.cursor_image = Some(CursorImage { texture: handle, size: [width, height], hotspot, batch });
+        self.set_cursor_visible(false);
+        Ok(())
+    }
+
+    pub fn clear_cursor_image(&mut self) {
+        if self.surface_mut().cursor_image.take().is_some() {
+            self.set_cursor_visible(true);
+        }
+    }
+
+    // the shape batch that draw calls currently go to, depending on the target window and begin_mask /
+    // mask_inside / end_mask
+    fn shape_batch(&mut self) -> &mut Batch {
+        self.surface_mut().shape_batch()
     }
 
     pub fn triangle(&mut self, p1: [i32; 2], p2: [i32; 2], p3: [i32; 2], color: (f32, f32, f32)) {
-        self.batch.raw_quad(p1, p2, p3, p1, color, 0, self.options.use_pixel_space, 0.0)
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        self.shape_batch().raw_quad(p1, p2, p3, p1, color, 0, use_pixel_space, 0.0)
     }
 
     pub fn rect(&mut self, position: [i32; 2], width: i32, height: i32, color: (f32, f32, f32)) {
-        self.batch.add_quad(position, width, height, color, 0, self.options.use_pixel_space, 0.0)
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        self.shape_batch().add_quad(position, width, height, color, 0, use_pixel_space, 0.0)
     }
 
     pub fn circle(&mut self, position: [i32; 2], radius: i32, color: (f32, f32, f32)) {
-        self.batch.add_quad(
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        self.shape_batch().add_quad(
             [position[0] - radius, position[1] - radius], 
             radius * 2, 
             radius * 2, 
-            color, 1, self.options.use_pixel_space, 0.0)
+            color, 1, use_pixel_space, 0.0)
     }
 
     pub fn square(&mut self, position: [i32; 2], size: i32, color: (f32, f32, f32)) {
-        self.batch.add_quad(
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        self.shape_batch().add_quad(
             position,
             size,
             size,
             color,
             0,
-            self.options.use_pixel_space, 0.0);
+            use_pixel_space, 0.0);
     }
 
     pub fn line(&mut self, p1: [i32; 2], p2: [i32; 2], width: i32, color: (f32, f32, f32)) {
-        self.batch.add_quad(
-            p1, p2[0] - p1[0], p2[1] - p1[1], color, 2, self.options.use_pixel_space, width as f32 / 1000.0)
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        self.shape_batch().add_quad(
+            p1, p2[0] - p1[0], p2[1] - p1[1], color, 2, use_pixel_space, width as f32 / 1000.0)
 
     }
 
     // just a wrapper for drawing sets of triangles using the triangle function
     pub fn polygon(&mut self, points: Vec<i32>, color: (f32, f32, f32)) {
+        self.try_polygon(points, color).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_polygon(&mut self, points: Vec<i32>, color: (f32, f32, f32)) -> Result<(), Error> {
         let points : Vec<f32> = points.iter().map(|&x| x as f32).collect();
-        let indecies = earcutr::earcut(&points, &vec![], 2).unwrap();
+        let indecies = earcutr::earcut(&points, &[], 2)?;
         let points: Vec<[i32; 2]> = {
             let mut new_points: Vec<[i32; 2]> = Vec::new();
             let mut current_point = [0,0];
@@ -200,27 +772,575 @@ impl App {
                 self.triangle(points[indecies[index - 2]], points[indecies[index - 1]], points[indecies[index]], color)
             }
         }
+        Ok(())
     }
 
-    pub fn texture_quad(&mut self, position: [i32; 2], width: i32, height: i32, texture_path: &str) {
-        for texture_batch in self.texture_batches.iter_mut() {
-            if texture_path == texture_batch.path {
-                texture_batch.add_quad(position, width, height, (1.0,1.0,1.0), 0, self.options.use_pixel_space, 0.0);
-                return
+    pub fn texture_quad<'a>(&mut self, position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) {
+        self.try_texture_quad(position, width, height, texture).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // like texture_quad, but a texture that can't be loaded is reported instead of panicking
+    pub fn try_texture_quad<'a>(&mut self, position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) -> Result<(), Error> {
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        if let Some(texture_batch) = self.try_current_texture_batch(texture.into(), false)? {
+            texture_batch.add_quad(position, width, height, (1.0,1.0,1.0), 0, use_pixel_space, 0.0);
+        }
+        Ok(())
+    }
+
+    // the handle for a key, names that aren't loaded yet are loaded from disk. handles of unloaded
+    // textures resolve to None
+    fn resolve_texture(&mut self, texture: TextureKey) -> Result<Option<TextureHandle>, Error> {
+        match texture {
+            TextureKey::Handle(handle) => Ok(Some(handle).filter(|&handle| self.assets.contains(handle))),
+            TextureKey::Name(name) => match self.assets.handle(name) {
+                Some(handle) => Ok(Some(handle)),
+                None => Ok(Some(self.load_texture(name)?))
             }
         }
-        self.add_texture(texture_path, "generic_name");
-        println!("New texture created from the specified path. This message should only show up once for every new texture");
     }
 
-    pub fn add_texture(&mut self, path: &str, name: &str) {
-        for texture_batch in self.texture_batches.iter_mut() {
-            if name.to_string() == texture_batch.path {
-                return
+    fn current_texture_batch(&mut self, texture: TextureKey, tiled: bool) -> Option<&mut TextureBatch> {
+        self.try_current_texture_batch(texture, tiled).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // the texture batch that draw calls for this texture currently go to
+    fn try_current_texture_batch(&mut self, texture: TextureKey, tiled: bool) -> Result<Option<&mut TextureBatch>, Error> {
+        let handle = match self.resolve_texture(texture)? {
+            Some(handle) => handle,
+            None => return Ok(None)
+        };
+        let texture = self.assets.get(handle).unwrap();
+        let surface = self.surfaces[self.target].as_mut().unwrap();
+        Ok(Some(surface.texture_batch(handle, tiled, &self.programs, texture)))
+    }
+
+    pub fn texture_quad_with<'a>(&mut self, position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>, sprite_options: &SpriteOptions) {
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        if let Some(texture_batch) = self.current_texture_batch(texture.into(), false) {
+            let (texture_width, texture_height) = texture_batch.texture.dimensions();
+            let src_rect = [0, 0, texture_width as i32, texture_height as i32];
+            texture_batch.add_region(position, width, height, src_rect, sprite_options, use_pixel_space);
+        }
+    }
+
+    // covers the area with copies of the texture at its own pixel size, starting from the top left corner
+    pub fn texture_tiled<'a>(&mut self, position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) {
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        if let Some(texture_batch) = self.current_texture_batch(texture.into(), true) {
+            texture_batch.add_region(position, width, height, [0, 0, width, height], &SpriteOptions::default(), use_pixel_space);
+        }
+    }
+
+    pub fn set_texture_options<'a>(&mut self, texture: impl Into<TextureKey<'a>>, texture_options: TextureOptions) {
+        let handle = self.resolve_texture(texture.into()).unwrap_or_else(|error| panic!("{}", error));
+        if let Some(texture) = handle.and_then(|handle| self.assets.get(handle)) {
+            texture.options.set(texture_options);
+        }
+    }
+
+    // src_rect is [x, y, width, height] in pixels of the texture, dst_rect is [x, y, width, height] on screen
+    pub fn texture_region<'a>(&mut self, texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], dst_rect: [i32; 4]) {
+        self.texture_region_with(texture, src_rect, dst_rect, &SpriteOptions::default())
+    }
+
+    pub fn texture_region_with<'a>(&mut self, texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], dst_rect: [i32; 4], sprite_options: &SpriteOptions) {
+        let use_pixel_space = self.surface().options.use_pixel_space;
+        if let Some(texture_batch) = self.current_texture_batch(texture.into(), false) {
+            texture_batch.add_region([dst_rect[0], dst_rect[1]], dst_rect[2], dst_rect[3], src_rect, sprite_options, use_pixel_space);
+        }
+    }
+
+    // the built-in 8x8 font, size is the height and advance of a glyph. a newline starts a new line below.
+    // with set_text_font it's drawn in that font instead, white and size pixels high
+    pub fn text(&mut self, string: &str, position: [i32; 2], size: i32) {
+        self.draw_text_with(string, position, &TextOptions { size: size as f32, ..Default::default() })
+    }
+
+    // a .ttf or .otf file, loading the same path again returns the same font
+    pub fn load_font(&mut self, path: &str) -> Result<FontHandle, FontError> {
+        if let Some(index) = self.fonts.iter().position(|font| font.name == path) {
+            return Ok(FontHandle(index))
+        }
+        let bytes = std::fs::read(path).map_err(|error| FontError::Io(path.to_string(), error))?;
+        self.load_font_bytes(path, &bytes)
+    }
+
+    // for fonts embedded with include_bytes, name is what errors call the font and its atlas. loading
+    // the same name again returns the same font
+    pub fn load_font_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<FontHandle, FontError> {
+        if let Some(index) = self.fonts.iter().position(|font| font.name == name) {
+            return Ok(FontHandle(index))
+        }
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(|error| FontError::Parse(name.to_string(), error))?;
+        let atlas_name = format!("font {}", name);
+        let atlas = self.internal_dynamic_texture(&atlas_name, truetype::ATLAS_SIZE, truetype::ATLAS_SIZE).map_err(|error| FontError::Atlas(name.to_string(), error))?;
+        log::info!("loaded font '{}'", name);
+        self.fonts.push(TrueTypeFont::new(name, font, atlas));
+        Ok(FontHandle(self.fonts.len() - 1))
+    }
+
+    // size is in pixels, position is the top left corner of the first line. glyphs are rasterized the
+    // first time they're drawn at a size
+    pub fn draw_text(&mut self, font: FontHandle, string: &str, position: [i32; 2], size: f32, color: (f32, f32, f32)) {
+        self.draw_text_with(string, position, &TextOptions { font: Some(font), size, color, ..Default::default() })
+    }
+
+    // wrapped, aligned and anchored as the options say, position is where the anchor is on the left edge
+    pub fn draw_text_with(&mut self, string: &str, position: [i32; 2], options: &TextOptions) {
+        match options.font.or(self.text_font).filter(|font| font.0 < self.fonts.len()) {
+            Some(font) => {
+                let quads = self.truetype_quads(font.0, string, position, options);
+                let pages: Vec<TextureHandle> = (0 .. self.fonts[font.0].page_count()).map(|page| self.fonts[font.0].texture(page)).collect();
+                self.draw_glyph_quads(&pages, quads, options.color);
+            },
+            None => {
+                let quads = TextBlock::new(&self.font, string, position, options).quads(&mut self.font, options);
+                self.draw_glyph_quads(&[self.font.texture], quads, options.color);
             }
         }
+    }
 
-        self.texture_batches.push(TextureBatch::new(&self.display, self.options.window_width, self.options.window_height, path.to_string()))
+    // glyphs that didn't fit into the font's atlas get a new page and the text is laid out again
+    fn truetype_quads(&mut self, index: usize, string: &str, position: [i32; 2], options: &TextOptions) -> Vec<GlyphQuad> {
+        let block = TextBlock::new(&self.fonts[index], string, position, options);
+        let mut quads = block.quads(&mut self.fonts[index], options);
+        while self.fonts[index].needs_page() {
+            let font = &self.fonts[index];
+            let page_name = format!("font {} {}", font.name, font.page_count() + 1);
+            match self.internal_dynamic_texture(&page_name, truetype::ATLAS_SIZE, truetype::ATLAS_SIZE) {
+                Ok(page) => self.fonts[index].add_page(page),
+                Err(error) => {
+                    log::warn!("{}", FontError::Atlas(self.fonts[index].name.clone(), error));
+                    break
+                }
+            }
+            quads = block.quads(&mut self.fonts[index], options);
+        }
+        quads
+    }
+
+    // always with the built-in font, whatever set_text_font says, for what the app draws itself
+    fn bitmap_text(&mut self, string: &str, position: [i32; 2], size: f32) {
+        let options = TextOptions { size, ..Default::default() };
+        let quads = TextBlock::new(&self.font, string, position, &options).quads(&mut self.font, &options);
+        self.draw_glyph_quads(&[self.font.texture], quads, options.color);
+    }
+
+    fn draw_glyph_quads(&mut self, pages: &[TextureHandle], quads: Vec<GlyphQuad>, color: (f32, f32, f32)) {
+        let sprite_options = SpriteOptions { tint: color, ..Default::default() };
+        for quad in quads {
+            self.texture_region_with(pages[quad.page], quad.src_rect, quad.dst_rect, &sprite_options);
+        }
+    }
+
+    // [x, y, width, height] of the box draw_text_with would lay the text out in, without drawing it
+    pub fn measure_text(&self, string: &str, position: [i32; 2], options: &TextOptions) -> [i32; 4] {
+        match options.font.or(self.text_font).and_then(|font| self.fonts.get(font.0)) {
+            Some(font) => TextBlock::new(font, string, position, options).bounds(),
+            None => TextBlock::new(&self.font, string, position, options).bounds()
+        }
+    }
+
+    // makes text() draw with a loaded font instead of the built-in one, None goes back to it
+    pub fn set_text_font(&mut self, font: Option<FontHandle>) {
+        self.text_font = font;
+    }
+
+    pub fn sprite(&mut self, sheet: &SpriteSheet, frame: usize, position: [i32; 2], width: i32, height: i32) {
+        if let Some(src_rect) = sheet.frame(frame) {
+            self.texture_region(sheet.path(), src_rect, [position[0], position[1], width, height]);
+        }
+    }
+
+    pub fn named_sprite(&mut self, sheet: &SpriteSheet, name: &str, position: [i32; 2], width: i32, height: i32) {
+        if let Some(src_rect) = sheet.named(name) {
+            self.texture_region(sheet.path(), src_rect, [position[0], position[1], width, height]);
+        }
+    }
+
+    // instances are positioned in pixels, this undoes the screen space conversion when pixel coords are off
+    fn instance_position(&self, position: [f32; 2]) -> [f32; 2] {
+        if self.surface().options.use_pixel_space {
+            return position
+        }
+        [position[0] * self.surface().options.window_width as f32 / 2.0, -position[1] * self.surface().options.window_height as f32 / 2.0]
+    }
+
+    pub fn instance_rect(&mut self, position: [f32; 2], width: f32, height: f32, rotation: f32, color: (f32, f32, f32)) {
+        let i_position = self.instance_position(position);
+        self.surface_mut().instances().shapes.push(Instance {
+            i_position,
+            i_size: [width, height],
+            i_rotation: rotation,
+            i_color: [color.0, color.1, color.2, 1.0],
+            i_uv_rect: [0.0, 0.0, 1.0, 1.0],
+            i_style: INSTANCE_STYLE_RECT
+        })
+    }
+
+    pub fn instance_circle(&mut self, position: [f32; 2], radius: f32, color: (f32, f32, f32)) {
+        let i_position = self.instance_position(position);
+        self.surface_mut().instances().shapes.push(Instance {
+            i_position: [i_position[0] - radius, i_position[1] - radius],
+            i_size: [radius * 2.0, radius * 2.0],
+            i_rotation: 0.0,
+            i_color: [color.0, color.1, color.2, 1.0],
+            i_uv_rect: [0.0, 0.0, 1.0, 1.0],
+            i_style: INSTANCE_STYLE_CIRCLE
+        })
+    }
+
+    pub fn instance_sprite<'a>(&mut self, position: [f32; 2], width: f32, height: f32, rotation: f32, texture: impl Into<TextureKey<'a>>) {
+        self.push_sprite_instance([position[0], position[1], width, height], rotation, texture.into(), None, (1.0, 1.0, 1.0, 1.0))
+    }
+
+    // only the src_rect part of the texture (in pixels from its top left, like texture_region), tinted by
+    // color as (r, g, b, alpha). dst_rect is [x, y, width, height] like position, width and height above
+    pub fn instance_sprite_region<'a>(&mut self, dst_rect: [f32; 4], rotation: f32, texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], color: (f32, f32, f32, f32)) {
+        self.push_sprite_instance(dst_rect, rotation, texture.into(), Some(src_rect), color)
+    }
+
+    fn push_sprite_instance(&mut self, dst_rect: [f32; 4], rotation: f32, texture: TextureKey, src_rect: Option<[i32; 4]>, color: (f32, f32, f32, f32)) {
+        let handle = match self.resolve_texture(texture).unwrap_or_else(|error| panic!("{}", error)) {
+            Some(handle) => handle,
+            None => return
+        };
+        let texture = self.assets.get(handle);
+        let i_uv_rect = match (src_rect, texture.as_ref()) {
+            (Some(src_rect), Some(texture)) => {
+                let (texture_width, texture_height) = texture.dimensions();
+                let [x, y, width, height] = src_rect.map(|value| value as f32);
+                [x / texture_width as f32, y / texture_height as f32, width / texture_width as f32, height / texture_height as f32]
+            },
+            _ => [0.0, 0.0, 1.0, 1.0]
+        };
+        let instance = Instance {
+            i_position: self.instance_position([dst_rect[0], dst_rect[1]]),
+            i_size: [dst_rect[2], dst_rect[3]],
+            i_rotation: rotation,
+            i_color: [color.0, color.1, color.2, color.3],
+            i_uv_rect,
+            i_style: INSTANCE_STYLE_SPRITE
+        };
+        self.surface_mut().instances().sprite_batch(handle, texture).push(instance);
+    }
+
+    // everything drawn between begin_mask and mask_inside / mask_outside only writes the stencil buffer
+    pub fn begin_mask(&mut self) {
+        let surface = self.surfaces[self.target].as_mut().unwrap();
+        surface.begin_mask(&self.programs);
+    }
+
+    pub fn mask_inside(&mut self) {
+        self.set_mask_mode(MaskMode::Inside)
+    }
+
+    pub fn mask_outside(&mut self) {
+        self.set_mask_mode(MaskMode::Outside)
+    }
+
+    fn set_mask_mode(&mut self, mode: MaskMode) {
+        let surface = self.surface_mut();
+        match surface.draw_target {
+            DrawTarget::Mask(layer) | DrawTarget::Masked(layer) => {
+                surface.mask_layers[layer].mode = mode;
+                surface.draw_target = DrawTarget::Masked(layer);
+            },
+            DrawTarget::Base => ()
+        }
+    }
+
+    pub fn end_mask(&mut self) {
+        self.surface_mut().draw_target = DrawTarget::Base;
+    }
+
+    // loads the file at path (once) and makes it drawable under name as well
+    pub fn add_texture(&mut self, path: &str, name: &str) -> TextureHandle {
+        self.try_add_texture(path, name).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // every call adds a reference that unload_texture gives back, like load_texture
+    pub fn try_add_texture(&mut self, path: &str, name: &str) -> Result<TextureHandle, TextureError> {
+        let handle = self.load_texture(path)?;
+        self.assets.alias(handle, name);
+        Ok(handle)
+    }
+
+    // like add_texture, but reports a missing or undecodable file instead of panicking. every call
+    // adds a reference that unload_texture gives back
+    pub fn load_texture(&mut self, path: &str) -> Result<TextureHandle, TextureError> {
+        if let Some(handle) = self.assets.handle(path) {
+            self.assets.retain(handle);
+            return Ok(handle)
+        }
+        let texture = Rc::new(Texture::from_file(new_texture(path, self.display())?, path));
+        log::info!("loaded texture '{}'", path);
+        Ok(self.assets.insert(path, texture))
+    }
+
+    pub fn preload_textures(&mut self, paths: &[&str]) -> Result<Vec<TextureHandle>, TextureError> {
+        paths.iter().map(|path| self.load_texture(path)).collect()
+    }
+
+    pub fn texture_handle(&self, name: &str) -> Option<TextureHandle> {
+        self.assets.handle(name)
+    }
+
+    // makes the texture drawable under another name, returns false for unloaded handles
+    pub fn alias_texture(&mut self, handle: TextureHandle, name: &str) -> bool {
+        self.assets.alias(handle, name)
+    }
+
+    // gives back one reference, the texture and its batches are freed once none are left
+    pub fn unload_texture(&mut self, handle: TextureHandle) {
+        // text is drawn with the font atlases, so they stay loaded whatever the app unloads
+        if self.assets.is_internal(handle) {
+            log::warn!("texture {:?} belongs to blithaven and can't be unloaded", handle);
+            return
+        }
+        if !self.assets.release(handle) {
+            return
+        }
+        log::debug!("unloaded texture {:?}", handle);
+        for surface in self.surfaces.iter_mut().flatten() {
+            surface.remove_texture(handle);
+        }
+    }
+
+    // registers a texture under `name` so it can be blitted like a path, e.g. for include_bytes! assets.
+    // formats without a signature, like tga, need the extension in name
+    pub fn add_texture_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<TextureHandle, TextureError> {
+        if let Some(handle) = self.assets.handle(name) {
+            self.assets.retain(handle);
+            return Ok(handle)
+        }
+        let image = texture::decode_bytes(name, bytes)?;
+        self.add_texture_rgba(name, &image)
+    }
+
+    pub fn add_texture_image(&mut self, name: &str, image: &DynamicImage) -> Result<TextureHandle, TextureError> {
+        self.add_texture_rgba(name, &image.to_rgba8())
+    }
+
+    pub fn add_texture_rgba(&mut self, name: &str, image: &RgbaImage) -> Result<TextureHandle, TextureError> {
+        if let Some(handle) = self.assets.handle(name) {
+            self.assets.retain(handle);
+            return Ok(handle)
+        }
+        let texture = Rc::new(Texture::new(texture::upload(self.display(), name, image)?));
+        Ok(self.assets.insert(name, texture))
+    }
+
+    // calls back with the frame of the target window that's being drawn, once it's finished. the frame is
+    // copied before it's shown, so only frames a screenshot asked for cost a copy
+    pub fn screenshot_image<F>(&mut self, callback: F) where F: 'static + FnOnce(Result<RgbaImage, CaptureError>) {
+        self.surface_mut().captures.push(PendingCapture::Image(Box::new(callback)));
+    }
+
+    // saves the frame of the target window that's being drawn once it's finished, the format comes from
+    // the file extension. the result is logged
+    pub fn screenshot(&mut self, path: &str) {
+        self.surface_mut().captures.push(PendingCapture::Save(path.to_string()));
+    }
+
+    // pressing this key saves a timestamped png into the working directory
+    pub fn set_screenshot_key(&mut self, key: Option<VirtualKeyCode>) {
+        self.screenshot_key = key;
+    }
+
+    // while enabled, textures loaded from files are reloaded when the file changes on disk
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = match enabled {
+            true => Some(HotReload::new()),
+            false => None
+        };
+    }
+
+    // returns right away with a handle that draws the placeholder texture until the file is decoded
+    // on a worker thread and uploaded, at the start of a later frame
+    pub fn load_texture_async(&mut self, path: &str) -> Result<TextureHandle, TextureError> {
+        if let Some(handle) = self.assets.handle(path) {
+            self.assets.retain(handle);
+            return Ok(handle)
+        }
+        // every pending texture gets its own copy of the placeholder, so its options stay its own
+        let placeholder = Rc::new(Texture::new(texture::upload(self.display(), path, &self.loader.placeholder)?));
+        let handle = self.assets.insert(path, placeholder);
+        self.loader.request(handle, path);
+        Ok(handle)
+    }
+
+    pub fn preload_textures_async(&mut self, paths: &[&str]) -> Result<Vec<TextureHandle>, TextureError> {
+        paths.iter().map(|path| self.load_texture_async(path)).collect()
+    }
+
+    // counts the async loads since the last time everything finished loading
+    pub fn loading_progress(&self) -> LoadingProgress {
+        self.loader.progress()
+    }
+
+    // false while the placeholder is still drawn in its place
+    pub fn is_texture_loaded(&self, handle: TextureHandle) -> bool {
+        self.assets.contains(handle) && !self.loader.is_pending(handle)
+    }
+
+    // async loads that failed since the last call, their handles keep drawing the placeholder
+    pub fn take_loading_errors(&mut self) -> Vec<TextureError> {
+        self.loader.take_errors()
+    }
+
+    // used for loads started after this, the default is a magenta and black checkerboard
+    pub fn set_placeholder_texture(&mut self, image: &RgbaImage) {
+        self.loader.placeholder = image.clone();
+    }
+
+    fn upload_loaded_textures(&mut self) {
+        let start = Instant::now();
+        while start.elapsed() < loader::UPLOAD_BUDGET {
+            let (handle, path, image) = match self.loader.ready() {
+                Some(decoded) => decoded,
+                None => return
+            };
+            // unloaded while it was decoding
+            if !self.assets.contains(handle) {
+                self.loader.loaded();
+                continue
+            }
+            match image.and_then(|image| texture::upload(self.display(), &path, &image)) {
+                Ok(texture) => {
+                    log::info!("loaded texture '{}' in the background", path);
+                    self.replace_texture(handle, Texture::from_file(texture, &path));
+                    self.loader.loaded();
+                },
+                Err(error) => {
+                    log::warn!("{}, drawing the placeholder instead", error);
+                    self.loader.failed(error);
+                }
+            }
+        }
+    }
+
+    fn reload_changed_textures(&mut self) {
+        let hot_reload = match self.hot_reload.as_mut() {
+            Some(hot_reload) => hot_reload,
+            None => return
+        };
+        let file_paths = self.assets.file_paths();
+        let changed = hot_reload.changed_paths(file_paths.iter().map(|(_, path)| path.clone()).collect());
+
+        for (handle, path) in file_paths.into_iter().filter(|(_, path)| changed.contains(path)) {
+            // a file that is still being written fails to decode, the old texture stays until the next change
+            match new_texture(&path, self.display()) {
+                Ok(texture) => {
+                    log::info!("reloaded texture '{}'", path);
+                    self.replace_texture(handle, Texture::from_file(texture, &path));
+                },
+                Err(error) => log::warn!("{}", error)
+            }
+        }
+    }
+
+    // swaps the texture in every batch drawing it, so existing handles and names keep working
+    fn replace_texture(&mut self, handle: TextureHandle, texture: Texture) {
+        let old = match self.assets.get(handle) {
+            Some(old) => old,
+            None => return
+        };
+        texture.options.set(old.options.get());
+        let texture = Rc::new(texture);
+        self.assets.replace(handle, texture.clone());
+        for surface in self.surfaces.iter_mut().flatten() {
+            surface.replace_texture(&old, &texture);
+        }
+    }
+
+    // captures every finished frame until stop_recording, does nothing if a recording is already running
+    pub fn start_recording(&mut self, recording_options: RecordingOptions) {
+        if self.recorder.is_none() {
+            self.recorder = Some(Recorder::start(recording_options));
+        }
+    }
+
+    // blocks until the remaining frames are written, returns the number of frames recorded
+    pub fn stop_recording(&mut self) -> Result<u32, CaptureError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.stop(),
+            None => Ok(0)
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // gives the app a width x height grid of rgba pixels that is stretched over the window every frame,
+    // shapes and sprites are still drawn on top of it
+    pub fn pixel_mode(&mut self, width: u32, height: u32) -> Result<(), TextureError> {
+        let main = self.surfaces[0].as_ref().unwrap();
+        let pixel_buffer = PixelBuffer::new(&main.display, &mut self.assets, width, height, main.options.window_width, main.options.window_height, self.programs.texture.clone())?;
+        self.disable_pixel_mode();
+        self.pixel_buffer = Some(pixel_buffer);
+        Ok(())
+    }
+
+    pub fn disable_pixel_mode(&mut self) {
+        if let Some(pixel_buffer) = self.pixel_buffer.take() {
+            self.unload_texture(pixel_buffer.pixels.handle());
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if let Some(pixel_buffer) = self.pixel_buffer.as_mut() {
+            pixel_buffer.pixels.set_pixel(x, y, color);
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        match self.pixel_buffer.as_ref() {
+            Some(pixel_buffer) => pixel_buffer.pixels.get_pixel(x, y),
+            None => [0, 0, 0, 0]
+        }
+    }
+
+    pub fn fill(&mut self, color: [u8; 4]) {
+        if let Some(pixel_buffer) = self.pixel_buffer.as_mut() {
+            pixel_buffer.pixels.fill(color);
+        }
+    }
+
+    pub fn blit_image(&mut self, position: [i32; 2], image: &RgbaImage) {
+        if let Some(pixel_buffer) = self.pixel_buffer.as_mut() {
+            pixel_buffer.pixels.blit_image(position[0], position[1], image);
+        }
+    }
+
+    pub fn pixels_mut(&mut self) -> Option<&mut DynamicTexture> {
+        self.pixel_buffer.as_mut().map(|pixel_buffer| &mut pixel_buffer.pixels)
+    }
+
+    // an existing texture with the same name is replaced
+    pub fn dynamic_texture(&mut self, name: &str, width: u32, height: u32) -> Result<DynamicTexture, TextureError> {
+        dynamic_texture::byte_length(name, width, height)?;
+        let image = RgbaImage::new(width, height);
+        let texture = Texture::new(texture::upload_dynamic(self.display(), name, &image)?);
+        let handle = match self.assets.handle(name) {
+            Some(handle) => {
+                self.replace_texture(handle, texture);
+                handle
+            },
+            None => self.assets.insert(name, Rc::new(texture))
+        };
+        Ok(DynamicTexture::new(name, handle, width, height, self.assets.get(handle).unwrap()))
+    }
+
+    // a dynamic texture only the crate draws with, name is just for errors
+    fn internal_dynamic_texture(&mut self, name: &str, width: u32, height: u32) -> Result<DynamicTexture, TextureError> {
+        dynamic_texture::byte_length(name, width, height)?;
+        let image = RgbaImage::new(width, height);
+        let texture = Rc::new(Texture::new(texture::upload_dynamic(self.display(), name, &image)?));
+        let handle = self.assets.insert_internal(texture.clone());
+        Ok(DynamicTexture::new(name, handle, width, height, texture))
     }
 
     pub fn animate(&mut self, position: [i32; 2], width: i32, height: i32, animation: &str) {
@@ -275,11 +1395,21 @@ pub struct Batch {
     index_buffer: Vec<u16>,
     window_width: i32,
     window_height: i32,
-    program: Program
+    program: Rc<Program>
 }
 
 impl Batch {
-    fn new(display: &Display, window_width: i32, window_height: i32) -> Self {
+    fn new(window_width: i32, window_height: i32, program: Rc<Program>) -> Self {
+        Self { 
+            vertex_buffer: Vec::new(),
+            index_buffer: Vec::new(),
+            window_width,
+            window_height,
+            program
+        }
+    }
+
+    fn program(display: &Display) -> Result<Program, ProgramCreationError> {
         let fragment_shader = String::from("
             #version 140
 
@@ -322,6 +1452,11 @@ impl Batch {
               else {
                 f_color = v_color;
               }
+
+              // keeps the cut away parts of circles and lines out of the stencil buffer when masking
+              if (f_color.a == 0.0) {
+                discard;
+              }
             }
         ");
 
@@ -350,15 +1485,7 @@ impl Batch {
             }
         ");
 
-        let program = { Program::from_source(display, &vertex_shader, &fragment_shader, None).unwrap() };
-
-        Self { 
-            vertex_buffer: Vec::new(),
-            index_buffer: Vec::new(),
-            window_width,
-            window_height,
-            program
-        }
+        Program::from_source(display, &vertex_shader, &fragment_shader, None)
     }
 
     fn get_default_draw_params() -> DrawParameters<'static> {
@@ -369,9 +1496,12 @@ impl Batch {
         }
     }
 
-    fn draw(&mut self, frame: &mut Frame, display: &Display) {
-        self.window_width = display.get_framebuffer_dimensions().0 as i32;
-        self.window_height = display.get_framebuffer_dimensions().1 as i32;
+    fn resize(&mut self, window_width: i32, window_height: i32) {
+        self.window_width = window_width;
+        self.window_height = window_height;
+    }
+
+    fn draw_with_params(&mut self, frame: &mut Frame, display: &Display, draw_params: &DrawParameters) -> Result<(), Error> {
         let uniforms = glium::uniform! {
             matrix: [
                 [1.0, 0.0, 0.5, 0.0],
@@ -381,39 +1511,48 @@ impl Batch {
             ]
         };
 
+        // taken up front so a failed draw doesn't leave this frame's quads behind for the next one
+        let vertices = std::mem::take(&mut self.vertex_buffer);
+        let indices = std::mem::take(&mut self.index_buffer);
+        if !vertices.is_empty() {
+            stats::count_batch();
+        }
         let mut index_buffer_buffer: Vec<u16> = Vec::new();
         let mut vertex_buffer_buffer: Vec<Vertex> = Vec::new();
         let mut quad_count = 0;
         const QUADS_PER_DRAW: usize = 16380;
 
-        for i in 0 .. self.vertex_buffer.len() {
-            vertex_buffer_buffer.push(self.vertex_buffer[i]);
+        for i in 0 .. vertices.len() {
+            vertex_buffer_buffer.push(vertices[i]);
             
             if (i + 1) % 4 == 0 {
                 for j in 0 .. 6 {
-                    index_buffer_buffer.push(self.index_buffer[6 * quad_count + j]);
+                    index_buffer_buffer.push(indices[6 * quad_count + j]);
                 }
                 quad_count += 1;
             }
-            if quad_count == QUADS_PER_DRAW || i == self.vertex_buffer.len() - 1 {
-                let index_buffer = IndexBuffer::new(display, index::PrimitiveType::TrianglesList, &index_buffer_buffer).unwrap();
-                let vertex_buffer = VertexBuffer::new(display, &vertex_buffer_buffer).unwrap();
+            if quad_count == QUADS_PER_DRAW || i == vertices.len() - 1 {
+                let index_buffer = IndexBuffer::new(display, index::PrimitiveType::TrianglesList, &index_buffer_buffer)?;
+                let vertex_buffer = VertexBuffer::new(display, &vertex_buffer_buffer)?;
 
                 index_buffer_buffer = Vec::new();
                 vertex_buffer_buffer = Vec::new();
                 
-                frame.draw(&vertex_buffer, &index_buffer, &self.program, &uniforms, &Batch::get_default_draw_params()).unwrap();
+                frame.draw(&vertex_buffer, &index_buffer, &self.program, &uniforms, draw_params)?;
+                stats::count_draw(vertex_buffer.len(), false);
+                if quad_count == QUADS_PER_DRAW {
+                    log::trace!("split a shape batch after {} quads", QUADS_PER_DRAW);
+                }
 
                 quad_count = 0;
             }
         }
-
-        self.vertex_buffer = Vec::new();
-        self.index_buffer = Vec::new();
+        Ok(())
     }
 
     fn pixel_to_screenspace_invert_y_coord(&self, a: [f32; 2]) -> [f32; 2] {
-        return [a[0] / ( self.window_width as f32 / 4.0 ), -a[1] / ( self.window_height as f32 / 4.0 )]
+        let [x, y] = pixels_to_screen_space(a, [self.window_width, self.window_height]);
+        [x, -y]
     }
 
     pub fn add_quad(&mut self, position: [i32; 2], width: i32, height: i32, color: (f32, f32, f32), style: i32, use_pixel_space: bool, variator: f32) {
@@ -422,8 +1561,7 @@ impl Batch {
             position = self.pixel_to_screenspace_invert_y_coord(position);
         }
 
-        let width = width as f32 / ( self.window_width as f32 / 4.0 );
-        let height = height as f32 / ( self.window_height as f32 / 4.0 );
+        let [width, height] = pixels_to_screen_space([width as f32, height as f32], [self.window_width, self.window_height]);
 
         let index_buffer_size = self.vertex_buffer.len() as u16;
         self.index_buffer.push(index_buffer_size + 0);
@@ -483,26 +1621,49 @@ pub struct TextureBatch {
     index_buffer: Vec<u16>,
     window_width: i32,
     window_height: i32,
-    program: Program,
-    path: String,
-    texture: glium::texture::SrgbTexture2d,
-    textures: Vec<glium::texture::SrgbTexture2d>
+    program: Rc<Program>,
+    texture: Rc<Texture>,
+    textures: Vec<glium::texture::SrgbTexture2d>,
+    // tiled batches always sample with repeat wrapping, whatever the texture's own wrap is
+    tiled: bool
 }
 
 impl TextureBatch {
-    fn new(display: &Display, window_width: i32, window_height: i32, path: String) -> Self {
+    fn with_texture(window_width: i32, window_height: i32, program: Rc<Program>, texture: Rc<Texture>) -> Self {
+        Self { 
+            vertex_buffer: Vec::new(),
+            index_buffer: Vec::new(),
+            window_width,
+            window_height,
+            program,
+            texture,
+            textures: vec![],
+            tiled: false
+        }
+    }
 
+    fn program(display: &Display) -> Result<Program, ProgramCreationError> {
         let vertex_shader = String::from("
             #version 140
 
             in vec2 position;
+            in vec4 color;
+            in int style;
             in vec2 tex_coord;
+            in float variator;
+
+            out vec4 v_color;
+            flat out int v_style;
             out vec2 v_tex_coord;
+            out float v_variator;
 
             uniform mat4 matrix;
 
             void main() {
+                v_color = color;
+                v_style = style;
                 v_tex_coord = tex_coord;
+                v_variator = variator;
                 gl_Position = matrix * vec4(position, 0.0, 1.0);
             }
         ");
@@ -510,39 +1671,43 @@ impl TextureBatch {
         let fragment_shader = String::from("       
             #version 140
 
+            in vec4 v_color;
+            flat in int v_style;
             in vec2 v_tex_coord;
+            in float v_variator;
             out vec4 color;
 
             uniform sampler2D tex;
+            uniform float alpha_cutoff;
 
             void main() {
-                color = texture(tex, v_tex_coord);
+                vec4 texel = texture(tex, v_tex_coord);
+                if (v_style == 1) {
+                  // flash: blend towards a flat color by v_variator, keeping the sprite's shape
+                  color = vec4(mix(texel.rgb, v_color.rgb, v_variator), texel.a * v_color.a);
+                }
+                else {
+                  color = texel * v_color;
+                }
+                if (color.a <= alpha_cutoff) {
+                  discard;
+                }
             }
         ");
-        let program = Program::from_source(display, &vertex_shader, &fragment_shader, None).unwrap();
-
-        let texture = new_texture(&path, display);
+        Program::from_source(display, &vertex_shader, &fragment_shader, None)
+    }
 
-        Self { 
-            vertex_buffer: Vec::new(),
-            index_buffer: Vec::new(),
-            window_width,
-            window_height,
-            program,
-            path,
-            texture,
-            textures: vec![]
-        }
+    fn resize(&mut self, window_width: i32, window_height: i32) {
+        self.window_width = window_width;
+        self.window_height = window_height;
     }
 
-    fn draw(&mut self, frame: &mut Frame, display: &Display) {
-        self.window_width = display.get_framebuffer_dimensions().0 as i32;
-        self.window_height = display.get_framebuffer_dimensions().1 as i32;
+    // fragments with an alpha at or below alpha_cutoff are discarded, which is what lets sprites act as masks
+    fn draw_with_params(&mut self, frame: &mut Frame, display: &Display, draw_params: &DrawParameters, alpha_cutoff: f32) -> Result<(), Error> {
 
-        let behavior = glium::uniforms::SamplerBehavior {
-            minify_filter: uniforms::MinifySamplerFilter::Linear,
-            magnify_filter: uniforms::MagnifySamplerFilter::Nearest,
-            ..Default::default()
+        let behavior = match self.tiled {
+            true => self.texture.wrapped_sampler_behavior(Wrap::Repeat),
+            false => self.texture.sampler_behavior()
         };
 
         let uniforms = glium::uniform! {
@@ -552,42 +1717,51 @@ impl TextureBatch {
                 [0.0, 0.0, 1.0, 0.0],
                 [-1.0, 1.0, -1.0, 1.0f32],
             ],
-            tex: glium::uniforms::Sampler(&self.texture, behavior)
+            tex: glium::uniforms::Sampler(&self.texture.texture, behavior),
+            alpha_cutoff: alpha_cutoff
         };
 
+        let vertices = std::mem::take(&mut self.vertex_buffer);
+        let indices = std::mem::take(&mut self.index_buffer);
+        if !vertices.is_empty() {
+            stats::count_batch();
+        }
         let mut index_buffer_buffer: Vec<u16> = Vec::new();
         let mut vertex_buffer_buffer: Vec<Vertex> = Vec::new();
         let mut quad_count: usize = 0;
         const QUADS_PER_DRAW: usize = 16380;
 
-        for i in 0 .. self.vertex_buffer.len() {
-            vertex_buffer_buffer.push(self.vertex_buffer[i]);
+        for i in 0 .. vertices.len() {
+            vertex_buffer_buffer.push(vertices[i]);
             
             if (i + 1) % 4 == 0 {
                 for j in 0 .. 6 {
-                    index_buffer_buffer.push(self.index_buffer[6 * quad_count + j]);
+                    index_buffer_buffer.push(indices[6 * quad_count + j]);
                 }
                 quad_count += 1;
             }
-            if quad_count == QUADS_PER_DRAW || i == self.vertex_buffer.len() - 1 {
-                let index_buffer: IndexBuffer<u16> = IndexBuffer::new(display, index::PrimitiveType::TrianglesList, &index_buffer_buffer).unwrap();
-                let vertex_buffer: VertexBuffer<Vertex> = VertexBuffer::new(display, &vertex_buffer_buffer).unwrap();
+            if quad_count == QUADS_PER_DRAW || i == vertices.len() - 1 {
+                let index_buffer: IndexBuffer<u16> = IndexBuffer::new(display, index::PrimitiveType::TrianglesList, &index_buffer_buffer)?;
+                let vertex_buffer: VertexBuffer<Vertex> = VertexBuffer::new(display, &vertex_buffer_buffer)?;
 
                 index_buffer_buffer = Vec::new();
                 vertex_buffer_buffer = Vec::new();
                 
-                frame.draw(&vertex_buffer, &index_buffer, &self.program, &uniforms, &Batch::get_default_draw_params()).unwrap();
+                frame.draw(&vertex_buffer, &index_buffer, &self.program, &uniforms, draw_params)?;
+                stats::count_draw(vertex_buffer.len(), true);
+                if quad_count == QUADS_PER_DRAW {
+                    log::trace!("split a texture batch after {} quads", QUADS_PER_DRAW);
+                }
 
                 quad_count = 0;
             }
         }
-
-        self.vertex_buffer = Vec::new();
-        self.index_buffer = Vec::new();
+        Ok(())
     }
 
     fn pixel_to_screenspace_invert_y_coord(&self, a: [f32; 2]) -> [f32; 2] {
-        return [a[0] / ( self.window_width as f32 / 4.0 ), -a[1] / ( self.window_height as f32 / 4.0 )]
+        let [x, y] = pixels_to_screen_space(a, [self.window_width, self.window_height]);
+        [x, -y]
     }
 
     pub fn add_quad(&mut self, position: [i32; 2], width: i32, height: i32, color: (f32, f32, f32), style: i32, use_pixel_space: bool, variator: f32) {
@@ -596,8 +1770,7 @@ impl TextureBatch {
             position = self.pixel_to_screenspace_invert_y_coord(position);
         }
 
-        let width = width as f32 / ( self.window_width as f32 / 4.0 );
-        let height = height as f32 / ( self.window_height as f32 / 4.0 );
+        let [width, height] = pixels_to_screen_space([width as f32, height as f32], [self.window_width, self.window_height]);
 
         let index_buffer_size = self.vertex_buffer.len() as u16;
         self.index_buffer.push(index_buffer_size + 0);
@@ -615,6 +1788,43 @@ impl TextureBatch {
         self.vertex_buffer.push(Vertex { position: [position[0]        , position[1] - height], color, style, tex_coord: [0.0,0.0], variator });
     }
 
+    pub fn add_region(&mut self, position: [i32; 2], width: i32, height: i32, src_rect: [i32; 4], sprite_options: &SpriteOptions, use_pixel_space: bool) {
+        let mut position = Self::f32_2_array_to_i32_2(&position);
+        if use_pixel_space {
+            position = self.pixel_to_screenspace_invert_y_coord(position);
+        }
+
+        let [width, height] = pixels_to_screen_space([width as f32, height as f32], [self.window_width, self.window_height]);
+
+        // textures are uploaded upside down, so the top of the source rect is the larger v
+        let texture_width = self.texture.dimensions().0 as f32;
+        let texture_height = self.texture.dimensions().1 as f32;
+        let left = src_rect[0] as f32 / texture_width;
+        let right = (src_rect[0] + src_rect[2]) as f32 / texture_width;
+        let top = 1.0 - src_rect[1] as f32 / texture_height;
+        let bottom = 1.0 - (src_rect[1] + src_rect[3]) as f32 / texture_height;
+        let (left, right) = if sprite_options.flip_x { (right, left) } else { (left, right) };
+        let (top, bottom) = if sprite_options.flip_y { (bottom, top) } else { (top, bottom) };
+
+        let index_buffer_size = self.vertex_buffer.len() as u16;
+        self.index_buffer.push(index_buffer_size);
+        self.index_buffer.push(index_buffer_size + 1);
+        self.index_buffer.push(index_buffer_size + 2);
+
+        self.index_buffer.push(index_buffer_size);
+        self.index_buffer.push(index_buffer_size + 3);
+        self.index_buffer.push(index_buffer_size + 2);
+
+        let (color, style, variator) = match sprite_options.flash {
+            Some((flash, amount)) => ([flash.0, flash.1, flash.2, sprite_options.opacity], 1, amount),
+            None => ([sprite_options.tint.0, sprite_options.tint.1, sprite_options.tint.2, sprite_options.opacity], 0, 0.0)
+        };
+        self.vertex_buffer.push(Vertex { position: [position[0]        , position[1]         ], color, style, tex_coord: [left, top], variator });
+        self.vertex_buffer.push(Vertex { position: [position[0] + width, position[1]         ], color, style, tex_coord: [right, top], variator });
+        self.vertex_buffer.push(Vertex { position: [position[0] + width, position[1] - height], color, style, tex_coord: [right, bottom], variator });
+        self.vertex_buffer.push(Vertex { position: [position[0]        , position[1] - height], color, style, tex_coord: [left, bottom], variator });
+    }
+
     fn color_tuple_to_array(i: &(f32,f32,f32)) -> [f32; 4] {
         return [i.0,i.1,i.2,1.0]
     }
@@ -622,50 +1832,191 @@ impl TextureBatch {
         return [i[0] as f32, i[1] as f32]
     }
 
-    pub fn raw_quad(&mut self, p1: [i32; 2], p2: [i32; 2], p3: [i32; 2], p4: [i32; 2], color: (f32, f32, f32), style: i32, use_pixel_space: bool, variator: f32) {
-        let mut p1 = Self::f32_2_array_to_i32_2(&p1);
-        let mut p2 = Self::f32_2_array_to_i32_2(&p2);
-        let mut p3 = Self::f32_2_array_to_i32_2(&p3);
-        let mut p4 = Self::f32_2_array_to_i32_2(&p4);
+    pub fn raw_quad(&mut self, p1: [i32; 2], p2: [i32; 2], p3: [i32; 2], p4: [i32; 2], color: (f32, f32, f32), style: i32, use_pixel_space: bool, variator: f32) {
+        let mut p1 = Self::f32_2_array_to_i32_2(&p1);
+        let mut p2 = Self::f32_2_array_to_i32_2(&p2);
+        let mut p3 = Self::f32_2_array_to_i32_2(&p3);
+        let mut p4 = Self::f32_2_array_to_i32_2(&p4);
+
+        if use_pixel_space {
+            p1 = self.pixel_to_screenspace_invert_y_coord(p1);
+            p2 = self.pixel_to_screenspace_invert_y_coord(p2);
+            p3 = self.pixel_to_screenspace_invert_y_coord(p3);
+            p4 = self.pixel_to_screenspace_invert_y_coord(p4);
+        }
+
+        let index_buffer_size = self.vertex_buffer.len() as u16;
+        self.index_buffer.push(index_buffer_size + 0);
+        self.index_buffer.push(index_buffer_size + 1);
+        self.index_buffer.push(index_buffer_size + 2);
+
+        self.index_buffer.push(index_buffer_size + 0);
+        self.index_buffer.push(index_buffer_size + 3);
+        self.index_buffer.push(index_buffer_size + 2);
+
+        let color = Self::color_tuple_to_array(&color);
+        self.vertex_buffer.push(Vertex { position: p1, color, style, tex_coord: [0.0,1.0], variator });
+        self.vertex_buffer.push(Vertex { position: p2, color, style, tex_coord: [1.0,1.0], variator });
+        self.vertex_buffer.push(Vertex { position: p3, color, style, tex_coord: [1.0,0.0], variator });
+        self.vertex_buffer.push(Vertex { position: p4, color, style, tex_coord: [0.0,0.0], variator });
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SpriteOptions {
+    pub tint: (f32, f32, f32),
+    pub opacity: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // draws the sprite's shape in a flat color, the f32 is how far to blend towards it (0 to 1)
+    pub flash: Option<((f32, f32, f32), f32)>
+}
+
+impl Default for SpriteOptions {
+    fn default() -> Self {
+        Self {
+            tint: (1.0, 1.0, 1.0),
+            opacity: 1.0,
+            flip_x: false,
+            flip_y: false,
+            flash: None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MaskMode {
+    Inside,
+    Outside
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DrawTarget {
+    Base,
+    Mask(usize),
+    Masked(usize)
+}
+
+// the shapes and sprites of a mask, plus the content that is clipped by it
+struct MaskLayer {
+    mode: MaskMode,
+    mask: Batch,
+    mask_textures: BatchMap<(TextureHandle, bool), TextureBatch>,
+    mask_instances: Instances,
+    batch: Batch,
+    texture_batches: BatchMap<(TextureHandle, bool), TextureBatch>,
+    instances: Instances
+}
+
+impl MaskLayer {
+    // instance_batch is shared for its program and quad
+    fn new(window_width: i32, window_height: i32, program: Rc<Program>, instance_batch: &InstanceBatch) -> Self {
+        Self {
+            mode: MaskMode::Inside,
+            mask: Batch::new(window_width, window_height, program.clone()),
+            mask_textures: BatchMap::new(),
+            mask_instances: Instances::new(instance_batch.share(None)),
+            batch: Batch::new(window_width, window_height, program),
+            texture_batches: BatchMap::new(),
+            instances: Instances::new(instance_batch.share(None))
+        }
+    }
+
+    fn get_mask_draw_params() -> DrawParameters<'static> {
+        let stencil = draw_parameters::Stencil {
+            test_clockwise: draw_parameters::StencilTest::AlwaysPass,
+            test_counter_clockwise: draw_parameters::StencilTest::AlwaysPass,
+            reference_value_clockwise: 1,
+            reference_value_counter_clockwise: 1,
+            depth_pass_operation_clockwise: draw_parameters::StencilOperation::Replace,
+            depth_pass_operation_counter_clockwise: draw_parameters::StencilOperation::Replace,
+            .. Default::default()
+        };
+        glium::DrawParameters {
+            color_mask: (false, false, false, false),
+            stencil,
+            .. Default::default()
+        }
+    }
+
+    fn get_content_draw_params(mode: MaskMode) -> DrawParameters<'static> {
+        let test = match mode {
+            MaskMode::Inside => draw_parameters::StencilTest::IfEqual { mask: 0xff },
+            MaskMode::Outside => draw_parameters::StencilTest::IfNotEqual { mask: 0xff }
+        };
+        let stencil = draw_parameters::Stencil {
+            test_clockwise: test,
+            test_counter_clockwise: test,
+            reference_value_clockwise: 1,
+            reference_value_counter_clockwise: 1,
+            .. Default::default()
+        };
+        glium::DrawParameters {
+            stencil,
+            .. Batch::get_default_draw_params()
+        }
+    }
+
+    fn resize(&mut self, window_width: i32, window_height: i32) {
+        self.mask.resize(window_width, window_height);
+        self.batch.resize(window_width, window_height);
+        for texture_batch in self.mask_textures.iter_mut().chain(self.texture_batches.iter_mut()) {
+            texture_batch.resize(window_width, window_height);
+        }
+    }
 
-        if use_pixel_space {
-            p1 = self.pixel_to_screenspace_invert_y_coord(p1);
-            p2 = self.pixel_to_screenspace_invert_y_coord(p2);
-            p3 = self.pixel_to_screenspace_invert_y_coord(p3);
-            p4 = self.pixel_to_screenspace_invert_y_coord(p4);
+    fn draw(&mut self, frame: &mut Frame, display: &Display, window_size: [f32; 2], query: Option<&TimeElapsedQuery>) -> Result<(), Error> {
+        let mask_params = profiler::timed(Self::get_mask_draw_params(), query);
+        self.mask.draw_with_params(frame, display, &mask_params)?;
+        for texture_batch in self.mask_textures.iter_mut() {
+            texture_batch.draw_with_params(frame, display, &mask_params, 0.5)?;
         }
+        self.mask_instances.draw(frame, display, window_size, &mask_params)?;
 
-        let index_buffer_size = self.vertex_buffer.len() as u16;
-        self.index_buffer.push(index_buffer_size + 0);
-        self.index_buffer.push(index_buffer_size + 1);
-        self.index_buffer.push(index_buffer_size + 2);
+        let content_params = profiler::timed(Self::get_content_draw_params(self.mode), query);
+        self.batch.draw_with_params(frame, display, &content_params)?;
+        for texture_batch in self.texture_batches.iter_mut() {
+            texture_batch.draw_with_params(frame, display, &content_params, 0.0)?;
+        }
+        self.instances.draw(frame, display, window_size, &content_params)
+    }
+}
 
-        self.index_buffer.push(index_buffer_size + 0);
-        self.index_buffer.push(index_buffer_size + 3);
-        self.index_buffer.push(index_buffer_size + 2);
+fn logical_size(physical_size: [u32; 2], scale_factor: f64) -> [i32; 2] {
+    [(physical_size[0] as f64 / scale_factor).round() as i32, (physical_size[1] as f64 / scale_factor).round() as i32]
+}
 
-        let color = Self::color_tuple_to_array(&color);
-        self.vertex_buffer.push(Vertex { position: p1, color, style, tex_coord: [0.0,1.0], variator });
-        self.vertex_buffer.push(Vertex { position: p2, color, style, tex_coord: [1.0,1.0], variator });
-        self.vertex_buffer.push(Vertex { position: p3, color, style, tex_coord: [1.0,0.0], variator });
-        self.vertex_buffer.push(Vertex { position: p4, color, style, tex_coord: [0.0,0.0], variator });
-    }
+// the size a window is drawn at, the override wins over the scale factor the os reports for the window
+fn window_size(physical_size: [u32; 2], scale_factor: f64, scale_factor_override: Option<f64>) -> [i32; 2] {
+    logical_size(physical_size, scale_factor_override.unwrap_or(scale_factor))
 }
 
+// logical pixels to the 0..2 the batch matrix stretches over the window
+fn pixels_to_screen_space(pixels: [f32; 2], window_size: [i32; 2]) -> [f32; 2] {
+    [pixels[0] / (window_size[0] as f32 / 2.0), pixels[1] / (window_size[1] as f32 / 2.0)]
+}
 
-fn new_texture(path: &str, display: &Display) -> glium::texture::SrgbTexture2d {
-    let f = File::open(path).unwrap();
-    let mut reader = std::io::BufReader::new(f);
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer).unwrap();
+fn new_texture(path: &str, display: &Display) -> Result<glium::texture::SrgbTexture2d, TextureError> {
+    let image = texture::decode_file(path)?;
+    texture::upload(display, path, &image)
+}
 
-    let image: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> = image::load(std::io::Cursor::new(&buffer),
-                    image::ImageFormat::Png).unwrap().to_rgba8();
-    let image_dimensions = image.dimensions();
-    let image: glium::texture::RawImage2d<'_, u8> = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
+// compiled once when the window opens and shared by every batch, so batches created mid-frame can't fail
+struct Programs {
+    shape: Rc<Program>,
+    texture: Rc<Program>
+}
 
-    let texture = glium::texture::SrgbTexture2d::new(display, image).unwrap();
-    texture
+impl Programs {
+    fn new(display: &Display) -> Result<Self, ProgramCreationError> {
+        let start = Instant::now();
+        let programs = Self {
+            shape: Rc::new(Batch::program(display)?),
+            texture: Rc::new(TextureBatch::program(display)?)
+        };
+        log::debug!("compiled the shape and texture shaders in {:?}", start.elapsed());
+        Ok(programs)
+    }
 }
 
 pub struct Options {
@@ -690,16 +2041,31 @@ static mut PRESSED_BUTTONS: Vec<MouseButton> = Vec::new();
 static mut CURSOR_POSITION: [f32; 2] = [0.0,0.0];
 
 pub fn initialize(title: &str, width: u32, height: u32) -> EventLoop<()> {
+    initialize_with(WindowConfig::new(title, width, height))
+}
+
+pub fn try_initialize(title: &str, width: u32, height: u32) -> Result<EventLoop<()>, Error> {
+    try_initialize_with(WindowConfig::new(title, width, height))
+}
+
+pub fn initialize_with(config: WindowConfig) -> EventLoop<()> {
+    try_initialize_with(config).unwrap_or_else(|error| panic!("{}", error))
+}
+
+pub fn try_initialize_with(config: WindowConfig) -> Result<EventLoop<()>, Error> {
     unsafe {
-        let (app, event_loop) = App::new(title, width, height);
+        let (app, event_loop) = App::try_with_config(config)?;
         CONTEXT = Some ( app );
-        return event_loop
-    };
+        Ok(event_loop)
+    }
 }
 
 pub fn get_app() -> &'static mut App {
-    if unsafe { CONTEXT.is_none() } { panic!() }
-    return unsafe { CONTEXT.as_mut().unwrap() }
+    try_get_app().unwrap_or_else(|error| panic!("{}", error))
+}
+
+pub fn try_get_app() -> Result<&'static mut App, Error> {
+    unsafe { (*std::ptr::addr_of_mut!(CONTEXT)).as_mut() }.ok_or(Error::NotInitialized)
 }
 
 pub fn circle(position: [i32; 2], radius: i32, color: (f32,f32,f32)) {
@@ -714,9 +2080,185 @@ pub fn rect(position: [i32; 2], width: i32, height: i32, color: (f32,f32,f32)) {
     let app = get_app();
     app.rect([position[0] as i32, position[1] as i32], width as i32, height as i32, color)
 }
-pub fn blit(position: [i32; 2], width: i32, height: i32, texture_path: &str) {
+pub fn instance_rect(position: [f32; 2], width: f32, height: f32, rotation: f32, color: (f32,f32,f32)) {
+    let app = get_app();
+    app.instance_rect(position, width, height, rotation, color)
+}
+pub fn instance_circle(position: [f32; 2], radius: f32, color: (f32,f32,f32)) {
+    let app = get_app();
+    app.instance_circle(position, radius, color)
+}
+pub fn instance_sprite<'a>(position: [f32; 2], width: f32, height: f32, rotation: f32, texture: impl Into<TextureKey<'a>>) {
+    let app = get_app();
+    app.instance_sprite(position, width, height, rotation, texture)
+}
+pub fn instance_sprite_region<'a>(dst_rect: [f32; 4], rotation: f32, texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], color: (f32, f32, f32, f32)) {
+    let app = get_app();
+    app.instance_sprite_region(dst_rect, rotation, texture, src_rect, color)
+}
+pub fn begin_mask() {
+    let app = get_app();
+    app.begin_mask()
+}
+pub fn mask_inside() {
+    let app = get_app();
+    app.mask_inside()
+}
+pub fn mask_outside() {
+    let app = get_app();
+    app.mask_outside()
+}
+pub fn end_mask() {
+    let app = get_app();
+    app.end_mask()
+}
+pub fn blit<'a>(position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) {
+    let app = get_app();
+    app.texture_quad(position, width, height, texture)
+}
+pub fn try_blit<'a>(position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) -> Result<(), Error> {
+    let app = try_get_app()?;
+    app.try_texture_quad(position, width, height, texture)
+}
+pub fn load_texture(path: &str) -> Result<TextureHandle, TextureError> {
+    let app = get_app();
+    app.load_texture(path)
+}
+pub fn preload_textures(paths: &[&str]) -> Result<Vec<TextureHandle>, TextureError> {
+    let app = get_app();
+    app.preload_textures(paths)
+}
+pub fn load_texture_async(path: &str) -> Result<TextureHandle, TextureError> {
+    let app = get_app();
+    app.load_texture_async(path)
+}
+pub fn preload_textures_async(paths: &[&str]) -> Result<Vec<TextureHandle>, TextureError> {
+    let app = get_app();
+    app.preload_textures_async(paths)
+}
+pub fn loading_progress() -> LoadingProgress {
+    let app = get_app();
+    app.loading_progress()
+}
+pub fn is_texture_loaded(handle: TextureHandle) -> bool {
+    let app = get_app();
+    app.is_texture_loaded(handle)
+}
+pub fn take_loading_errors() -> Vec<TextureError> {
+    let app = get_app();
+    app.take_loading_errors()
+}
+pub fn set_placeholder_texture(image: &RgbaImage) {
+    let app = get_app();
+    app.set_placeholder_texture(image)
+}
+pub fn texture_handle(name: &str) -> Option<TextureHandle> {
+    let app = get_app();
+    app.texture_handle(name)
+}
+pub fn alias_texture(handle: TextureHandle, name: &str) -> bool {
+    let app = get_app();
+    app.alias_texture(handle, name)
+}
+pub fn unload_texture(handle: TextureHandle) {
+    let app = get_app();
+    app.unload_texture(handle)
+}
+pub fn add_texture_bytes(name: &str, bytes: &[u8]) -> Result<TextureHandle, TextureError> {
+    let app = get_app();
+    app.add_texture_bytes(name, bytes)
+}
+pub fn add_texture_image(name: &str, image: &DynamicImage) -> Result<TextureHandle, TextureError> {
+    let app = get_app();
+    app.add_texture_image(name, image)
+}
+pub fn add_texture_rgba(name: &str, image: &RgbaImage) -> Result<TextureHandle, TextureError> {
+    let app = get_app();
+    app.add_texture_rgba(name, image)
+}
+pub fn screenshot_image<F>(callback: F) where F: 'static + FnOnce(Result<RgbaImage, CaptureError>) {
+    let app = get_app();
+    app.screenshot_image(callback)
+}
+pub fn screenshot(path: &str) {
+    let app = get_app();
+    app.screenshot(path)
+}
+pub fn set_screenshot_key(key: Option<VirtualKeyCode>) {
+    let app = get_app();
+    app.set_screenshot_key(key)
+}
+pub fn set_hot_reload(enabled: bool) {
+    let app = get_app();
+    app.set_hot_reload(enabled)
+}
+pub fn start_recording(recording_options: RecordingOptions) {
+    let app = get_app();
+    app.start_recording(recording_options)
+}
+pub fn stop_recording() -> Result<u32, CaptureError> {
+    let app = get_app();
+    app.stop_recording()
+}
+pub fn is_recording() -> bool {
+    let app = get_app();
+    app.is_recording()
+}
+pub fn pixel_mode(width: u32, height: u32) -> Result<(), TextureError> {
+    let app = get_app();
+    app.pixel_mode(width, height)
+}
+pub fn disable_pixel_mode() {
+    let app = get_app();
+    app.disable_pixel_mode()
+}
+pub fn set_pixel(x: u32, y: u32, color: [u8; 4]) {
+    let app = get_app();
+    app.set_pixel(x, y, color)
+}
+pub fn get_pixel(x: u32, y: u32) -> [u8; 4] {
+    let app = get_app();
+    app.get_pixel(x, y)
+}
+pub fn fill(color: [u8; 4]) {
+    let app = get_app();
+    app.fill(color)
+}
+pub fn blit_image(position: [i32; 2], image: &RgbaImage) {
+    let app = get_app();
+    app.blit_image(position, image)
+}
+pub fn dynamic_texture(name: &str, width: u32, height: u32) -> Result<DynamicTexture, TextureError> {
+    let app = get_app();
+    app.dynamic_texture(name, width, height)
+}
+pub fn blit_with<'a>(position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>, sprite_options: SpriteOptions) {
+    let app = get_app();
+    app.texture_quad_with(position, width, height, texture, &sprite_options)
+}
+pub fn blit_region_with<'a>(texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], dst_rect: [i32; 4], sprite_options: SpriteOptions) {
+    let app = get_app();
+    app.texture_region_with(texture, src_rect, dst_rect, &sprite_options)
+}
+pub fn blit_tiled<'a>(position: [i32; 2], width: i32, height: i32, texture: impl Into<TextureKey<'a>>) {
+    let app = get_app();
+    app.texture_tiled(position, width, height, texture)
+}
+pub fn set_texture_options<'a>(texture: impl Into<TextureKey<'a>>, texture_options: TextureOptions) {
     let app = get_app();
-    app.texture_quad([position[0] as i32, position[1] as i32], width as i32, height as i32, texture_path)
+    app.set_texture_options(texture, texture_options)
+}
+pub fn blit_region<'a>(texture: impl Into<TextureKey<'a>>, src_rect: [i32; 4], dst_rect: [i32; 4]) {
+    let app = get_app();
+    app.texture_region(texture, src_rect, dst_rect)
+}
+pub fn sprite(sheet: &SpriteSheet, frame: usize, position: [i32; 2], width: i32, height: i32) {
+    let app = get_app();
+    app.sprite(sheet, frame, position, width, height)
+}
+pub fn named_sprite(sheet: &SpriteSheet, name: &str, position: [i32; 2], width: i32, height: i32) {
+    let app = get_app();
+    app.named_sprite(sheet, name, position, width, height)
 }
 pub fn animate(position: [i32; 2], width: i32, height: i32, animation: &str) {
     let app = get_app();
@@ -734,8 +2276,12 @@ struct Animation {
     current_frame: usize
 }
 
-pub fn run<F>(title: &str, window_width: u32, window_height: u32, mut loop_function: F) ->! where F: 'static + FnMut() {
-    let ev_loop = initialize(title, window_width, window_height);
+pub fn run<F>(title: &str, window_width: u32, window_height: u32, loop_function: F) ->! where F: 'static + FnMut() {
+    run_with(WindowConfig::new(title, window_width, window_height), loop_function)
+}
+
+pub fn run_with<F>(config: WindowConfig, mut loop_function: F) ->! where F: 'static + FnMut() {
+    let ev_loop = initialize_with(config);
     let app = get_app();
     start_loop(ev_loop, move | events | {
         loop_function();
@@ -744,15 +2290,20 @@ pub fn run<F>(title: &str, window_width: u32, window_height: u32, mut loop_funct
     });
 }
 
-pub fn start_loop_and_init<F>(title: &str, width: u32, height: u32, mut input_code: F)->! where F: 'static + FnMut(&Vec<Event<'_, ()>>) {
-    let ev_loop = initialize(title, width, height);
+pub fn start_loop_and_init<F>(title: &str, width: u32, height: u32, input_code: F)->! where F: 'static + FnMut(&Vec<Event<'_, ()>>) {
+    start_loop_and_init_with(WindowConfig::new(title, width, height), input_code)
+}
+
+pub fn start_loop_and_init_with<F>(config: WindowConfig, mut input_code: F)->! where F: 'static + FnMut(&Vec<Event<'_, ()>>) {
+    let ev_loop = initialize_with(config);
     let app = get_app();
     start_loop(ev_loop, move | events | {
         input_code(events);
 
+        // the global input functions follow the main window, the others have window_key_down and friends
         for event in events.iter() {
             match event {
-                Event::WindowEvent { event, ..} => match event {
+                Event::WindowEvent { event, window_id } if app.is_main_window(*window_id) => match event {
                     glutin::event::WindowEvent::KeyboardInput { input, .. } => {
                         if input.virtual_keycode.is_some() {
                             unsafe {
@@ -794,9 +2345,10 @@ pub fn start<F>(event_loop: EventLoop<()>, mut loop_function: F) ->! where F: 's
     start_loop(event_loop, move | events | {
         loop_function(events);
 
+        // the global input functions follow the main window, the others have window_key_down and friends
         for event in events.iter() {
             match event {
-                Event::WindowEvent { event, ..} => match event {
+                Event::WindowEvent { event, window_id } if app.is_main_window(*window_id) => match event {
                     glutin::event::WindowEvent::KeyboardInput { input, .. } => {
                         if input.virtual_keycode.is_some() {
                             unsafe {
@@ -864,8 +2416,12 @@ pub fn key_release(button: VirtualKeyCode) -> bool {
     }
 }
 
+// in logical pixels, like everything that's drawn
 pub fn mouse_pos() -> [f32; 2] {
-    unsafe { CURSOR_POSITION.clone() }
+    // the position is only tracked over the main window
+    let scale_factor = try_get_app().map_or(1.0, |app| app.surfaces[0].as_ref().unwrap().scale_factor(app.scale_factor_override)) as f32;
+    let position = unsafe { CURSOR_POSITION };
+    [position[0] / scale_factor, position[1] / scale_factor]
 }
 
 pub fn mouse_clicks() -> Vec<MouseButton> {
@@ -900,43 +2456,277 @@ pub fn mouse_released(button: MouseButton) -> bool {
     }
 }
 
+pub fn open_window(config: WindowConfig) -> WindowHandle {
+    let app = get_app();
+    app.open_window(config)
+}
+pub fn close_window(window: WindowHandle) {
+    let app = get_app();
+    app.close_window(window)
+}
+pub fn is_window_open(window: WindowHandle) -> bool {
+    let app = get_app();
+    app.is_window_open(window)
+}
+pub fn set_target_window(window: WindowHandle) -> bool {
+    let app = get_app();
+    app.set_target_window(window)
+}
+pub fn target_window() -> WindowHandle {
+    let app = get_app();
+    app.target_window()
+}
+pub fn window_events(window: WindowHandle) -> Vec<WindowEvent<'static>> {
+    let app = get_app();
+    app.window_events(window).to_vec()
+}
+pub fn window_dims(window: WindowHandle) -> Option<[u32; 2]> {
+    let app = get_app();
+    app.window_dims(window)
+}
+pub fn window_mouse_pos(window: WindowHandle) -> Option<[f32; 2]> {
+    let app = get_app();
+    app.window_mouse_pos(window)
+}
+pub fn window_key_down(window: WindowHandle, key: VirtualKeyCode) -> bool {
+    let app = get_app();
+    app.window_key_down(window, key)
+}
+pub fn window_mouse_down(window: WindowHandle, button: MouseButton) -> bool {
+    let app = get_app();
+    app.window_mouse_down(window, button)
+}
+pub fn window_resized(window: WindowHandle) -> bool {
+    let app = get_app();
+    app.window_resized(window)
+}
+
+// of the target window
 pub fn get_dims() -> [u32; 2] {
     let app = get_app();
-    return [app.options.window_width as u32, app.options.window_height as u32]
+    let options = &app.surface().options;
+    [options.window_width as u32, options.window_height as u32]
+}
+
+pub fn frame_stats() -> FrameStats {
+    let app = get_app();
+    app.frame_stats()
+}
+pub fn set_stats_overlay(enabled: bool) {
+    let app = get_app();
+    app.set_stats_overlay(enabled)
+}
+pub fn toggle_stats_overlay() {
+    let app = get_app();
+    app.toggle_stats_overlay()
+}
+pub fn set_profiling(enabled: bool) {
+    let app = get_app();
+    app.set_profiling(enabled)
+}
+pub fn is_profiling() -> bool {
+    let app = get_app();
+    app.is_profiling()
+}
+pub fn start_trace() {
+    let app = get_app();
+    app.start_trace()
+}
+pub fn stop_trace(path: &str) -> Result<usize, CaptureError> {
+    let app = get_app();
+    app.stop_trace(path)
+}
+pub fn set_target_fps(fps: u32) {
+    let app = get_app();
+    app.set_target_fps(fps)
+}
+pub fn delta_time() -> f32 {
+    let app = get_app();
+    app.delta_time()
+}
+pub fn elapsed() -> f32 {
+    let app = get_app();
+    app.elapsed()
+}
+pub fn set_fixed_update<F>(steps_per_second: u32, update: F) where F: 'static + FnMut(f32) {
+    let app = get_app();
+    app.set_fixed_update(steps_per_second, update)
+}
+pub fn clear_fixed_update() {
+    let app = get_app();
+    app.clear_fixed_update()
+}
+pub fn interpolation_alpha() -> f32 {
+    let app = get_app();
+    app.interpolation_alpha()
+}
+
+pub fn scale_factor() -> f64 {
+    let app = get_app();
+    app.scale_factor()
+}
+
+pub fn set_scale_factor_override(scale_factor: Option<f64>) {
+    let app = get_app();
+    app.set_scale_factor_override(scale_factor)
+}
+pub fn set_title(title: &str) {
+    let app = get_app();
+    app.set_title(title)
+}
+pub fn window_mode() -> WindowMode {
+    let app = get_app();
+    app.window_mode()
+}
+pub fn set_window_mode(window_mode: WindowMode) {
+    let app = get_app();
+    app.set_window_mode(window_mode)
+}
+pub fn toggle_fullscreen() {
+    let app = get_app();
+    app.toggle_fullscreen()
+}
+pub fn set_window_size(width: u32, height: u32) {
+    let app = get_app();
+    app.set_window_size(width, height)
+}
+pub fn set_window_position(x: i32, y: i32) {
+    let app = get_app();
+    app.set_window_position(x, y)
+}
+pub fn window_position() -> Option<[i32; 2]> {
+    let app = get_app();
+    app.window_position()
+}
+pub fn set_minimized(minimized: bool) {
+    let app = get_app();
+    app.set_minimized(minimized)
+}
+pub fn set_maximized(maximized: bool) {
+    let app = get_app();
+    app.set_maximized(maximized)
+}
+pub fn is_maximized() -> bool {
+    let app = get_app();
+    app.is_maximized()
+}
+pub fn is_minimized() -> bool {
+    let app = get_app();
+    app.is_minimized()
+}
+pub fn is_focused() -> bool {
+    let app = get_app();
+    app.is_focused()
+}
+pub fn set_cursor_visible(visible: bool) {
+    let app = get_app();
+    app.set_cursor_visible(visible)
+}
+pub fn set_cursor_grab(grab: bool) -> Result<(), Error> {
+    let app = get_app();
+    app.set_cursor_grab(grab)
+}
+pub fn set_cursor_icon(cursor_icon: CursorIcon) {
+    let app = get_app();
+    app.set_cursor_icon(cursor_icon)
+}
+pub fn set_cursor_image<'a>(texture: impl Into<TextureKey<'a>>, width: i32, height: i32, hotspot: [i32; 2]) -> Result<(), Error> {
+    let app = get_app();
+    app.set_cursor_image(texture, width, height, hotspot)
+}
+pub fn clear_cursor_image() {
+    let app = get_app();
+    app.clear_cursor_image()
 }
 
 pub fn text(string: &str, pos: [i32; 2], size: i32) {
-    for (i, char) in string.to_uppercase().chars().enumerate() {
-
-        match char {
-            'B' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/b.png"),
-            'A' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/a.png"),
-            'C' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/c.png"),
-            'D' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/d.png"),
-            'E' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/e.png"),
-            'F' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/f.png"),
-            'G' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/g.png"),
-            'H' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/h.png"),
-            'I' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/i.png"),
-            'J' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/j.png"),
-            'K' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/k.png"),
-            'L' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/l.png"),
-            'M' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/m.png"),
-            'N' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/n.png"),
-            'O' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/o.png"),
-            'P' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/p.png"),
-            'Q' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/q.png"),
-            'R' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/r.png"),
-            'S' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/s.png"),
-            'T' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/t.png"),
-            'U' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/u.png"),
-            'V' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/v.png"),
-            'W' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/w.png"),
-            'X' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/x.png"),
-            'Y' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/y.png"),
-            'Z' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/z.png"),
-            '!' => blit([pos[0] + size * i as i32, pos[1]], size, size, "src/assets/font/!.png"),
-            _ => ()
+    let app = get_app();
+    app.text(string, pos, size)
+}
+pub fn load_font(path: &str) -> Result<FontHandle, FontError> {
+    let app = get_app();
+    app.load_font(path)
+}
+pub fn load_font_bytes(name: &str, bytes: &[u8]) -> Result<FontHandle, FontError> {
+    let app = get_app();
+    app.load_font_bytes(name, bytes)
+}
+pub fn draw_text(font: FontHandle, string: &str, position: [i32; 2], size: f32, color: (f32, f32, f32)) {
+    let app = get_app();
+    app.draw_text(font, string, position, size, color)
+}
+pub fn draw_text_with(string: &str, position: [i32; 2], options: TextOptions) {
+    let app = get_app();
+    app.draw_text_with(string, position, &options)
+}
+pub fn measure_text(string: &str, position: [i32; 2], options: TextOptions) -> [i32; 4] {
+    let app = get_app();
+    app.measure_text(string, position, &options)
+}
+pub fn set_text_font(font: Option<FontHandle>) {
+    let app = get_app();
+    app.set_text_font(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what the batch matrix does to the 0..2 space, it moves the top left corner to -1, 1
+    fn to_clip(screen_space: [f32; 2]) -> [f32; 2] {
+        [screen_space[0] - 1.0, screen_space[1] + 1.0]
+    }
+
+    #[test]
+    fn logical_size_divides_by_the_scale_factor() {
+        assert_eq!(logical_size([1920, 1080], 1.0), [1920, 1080]);
+        assert_eq!(logical_size([1920, 1080], 1.5), [1280, 720]);
+        assert_eq!(logical_size([1920, 1080], 2.0), [960, 540]);
+        // odd physical sizes round to the nearest logical pixel
+        assert_eq!(logical_size([1001, 751], 2.0), [501, 376]);
+    }
+
+    #[test]
+    fn the_override_wins_over_the_scale_factor() {
+        assert_eq!(window_size([1920, 1080], 2.0, None), [960, 540]);
+        assert_eq!(window_size([1920, 1080], 2.0, Some(1.0)), [1920, 1080]);
+        assert_eq!(window_size([1920, 1080], 1.0, Some(1.5)), [1280, 720]);
+    }
+
+    #[test]
+    fn the_override_survives_a_scale_factor_change() {
+        // set before the window moves to a screen with twice the scale factor, which doubles its physical size
+        let scale_factor_override = Some(1.0);
+        assert_eq!(window_size([800, 600], 1.0, scale_factor_override), [800, 600]);
+        assert_eq!(window_size([1600, 1200], 2.0, scale_factor_override), [1600, 1200]);
+
+        // set after the change, then cleared again
+        assert_eq!(window_size([1600, 1200], 2.0, None), [800, 600]);
+        assert_eq!(window_size([1600, 1200], 2.0, Some(1.0)), [1600, 1200]);
+        assert_eq!(window_size([1600, 1200], 2.0, None), [800, 600]);
+    }
+
+    #[test]
+    fn pixels_cover_the_window_at_every_scale_factor() {
+        for scale_factor in [1.0, 1.5, 2.0] {
+            let [width, height] = window_size([1920, 1080], scale_factor, None);
+            let clip = |pixel: [f32; 2]| {
+                let [x, y] = pixels_to_screen_space(pixel, [width, height]);
+                to_clip([x, -y])
+            };
+            assert_eq!(clip([0.0, 0.0]), [-1.0, 1.0], "top left at {}", scale_factor);
+            assert_eq!(clip([width as f32 / 2.0, height as f32 / 2.0]), [0.0, 0.0], "center at {}", scale_factor);
+            assert_eq!(clip([width as f32, height as f32]), [1.0, -1.0], "bottom right at {}", scale_factor);
+        }
+    }
+
+    #[test]
+    fn sizes_scale_with_the_window() {
+        for scale_factor in [1.0, 1.5, 2.0] {
+            let window = window_size([1920, 1080], 1.0, Some(scale_factor));
+            // a quad as big as the window spans all of the 0..2 space
+            assert_eq!(pixels_to_screen_space([window[0] as f32, window[1] as f32], window), [2.0, 2.0]);
+            assert_eq!(pixels_to_screen_space([window[0] as f32 / 4.0, 0.0], window), [0.5, 0.0]);
         }
     }
 }
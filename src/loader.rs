@@ -0,0 +1,127 @@
+use std::{collections::HashSet, sync::{mpsc, Arc, Mutex}, thread, time::Duration};
+use image::RgbaImage;
+use crate::{texture::{self, TextureError}, TextureHandle};
+
+// uploading is what stalls the frame, ready textures past this budget wait for the next one
+pub(crate) const UPLOAD_BUDGET: Duration = Duration::from_millis(8);
+const MAX_WORKERS: usize = 4;
+
+type Job = (TextureHandle, String);
+pub(crate) type Decoded = (TextureHandle, String, Result<RgbaImage, TextureError>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize
+}
+
+impl LoadingProgress {
+    // 1.0 when nothing is loading
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0
+        }
+        (self.loaded + self.failed) as f32 / self.total as f32
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+}
+
+// decodes files on worker threads, the results are uploaded on the main thread by App::finish
+pub(crate) struct Loader {
+    jobs: Option<mpsc::Sender<Job>>,
+    decoded_sender: mpsc::Sender<Decoded>,
+    decoded: mpsc::Receiver<Decoded>,
+    pending: HashSet<TextureHandle>,
+    progress: LoadingProgress,
+    errors: Vec<TextureError>,
+    pub placeholder: RgbaImage
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        let (decoded_sender, decoded) = mpsc::channel();
+        Self {
+            jobs: None,
+            decoded_sender,
+            decoded,
+            pending: HashSet::new(),
+            progress: LoadingProgress { loaded: 0, failed: 0, total: 0 },
+            errors: Vec::new(),
+            placeholder: checkerboard()
+        }
+    }
+
+    // the worker threads are only started by the first request
+    fn jobs(&mut self) -> &mpsc::Sender<Job> {
+        let decoded_sender = &self.decoded_sender;
+        self.jobs.get_or_insert_with(|| {
+            let (jobs, receiver) = mpsc::channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            let workers = thread::available_parallelism().map_or(1, |count| count.get()).min(MAX_WORKERS);
+            for _ in 0 .. workers {
+                let receiver = receiver.clone();
+                let decoded_sender = decoded_sender.clone();
+                thread::spawn(move || loop {
+                    let job = receiver.lock().unwrap().recv();
+                    let (handle, path) = match job {
+                        Ok(job) => job,
+                        Err(_) => return
+                    };
+                    let image = texture::decode_file(&path);
+                    if decoded_sender.send((handle, path, image)).is_err() {
+                        return
+                    }
+                });
+            }
+            jobs
+        })
+    }
+
+    pub fn request(&mut self, handle: TextureHandle, path: &str) {
+        // a new round of loading starts the progress over, so a loading screen counts from zero
+        if self.progress.is_done() {
+            self.progress = LoadingProgress { loaded: 0, failed: 0, total: 0 };
+        }
+        self.progress.total += 1;
+        self.pending.insert(handle);
+        self.jobs().send((handle, path.to_string())).unwrap();
+    }
+
+    pub fn is_pending(&self, handle: TextureHandle) -> bool {
+        self.pending.contains(&handle)
+    }
+
+    pub fn progress(&self) -> LoadingProgress {
+        self.progress
+    }
+
+    pub fn take_errors(&mut self) -> Vec<TextureError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn loaded(&mut self) {
+        self.progress.loaded += 1;
+    }
+
+    pub fn failed(&mut self, error: TextureError) {
+        self.progress.failed += 1;
+        self.errors.push(error);
+    }
+
+    pub fn ready(&mut self) -> Option<Decoded> {
+        let decoded = self.decoded.try_recv().ok()?;
+        self.pending.remove(&decoded.0);
+        Some(decoded)
+    }
+}
+
+fn checkerboard() -> RgbaImage {
+    RgbaImage::from_fn(2, 2, |x, y| match (x + y) % 2 {
+        0 => image::Rgba([255, 0, 255, 255]),
+        _ => image::Rgba([0, 0, 0, 255])
+    })
+}
@@ -0,0 +1,42 @@
+use std::rc::Rc;
+use glium::{draw_parameters::TimeElapsedQuery, Display, Frame, Program};
+use crate::{profiler, Batch, Error, TextureBatch, Vertex};
+use crate::assets::Assets;
+use crate::dynamic_texture::{self, DynamicTexture};
+use crate::texture::{self, Texture, TextureError, TextureOptions, Filter};
+
+// a cpu side image stretched over the whole window, drawn underneath the shape and sprite batches
+pub(crate) struct PixelBuffer {
+    pub pixels: DynamicTexture,
+    batch: TextureBatch
+}
+
+impl PixelBuffer {
+    // the texture is registered as "pixel buffer", so the frame can also be blitted like any other texture
+    pub fn new(display: &Display, assets: &mut Assets, width: u32, height: u32, window_width: i32, window_height: i32, program: Rc<Program>) -> Result<Self, TextureError> {
+        dynamic_texture::byte_length("pixel buffer", width, height)?;
+        let texture = texture::upload_dynamic(display, "pixel buffer", &image::RgbaImage::new(width, height))?;
+        let texture = Rc::new(Texture::new(texture));
+        texture.options.set(TextureOptions { min_filter: Filter::Nearest, mag_filter: Filter::Nearest, ..Default::default() });
+
+        let handle = assets.insert("pixel buffer", texture.clone());
+
+        Ok(Self {
+            pixels: DynamicTexture::new("pixel buffer", handle, width, height, texture.clone()),
+            batch: TextureBatch::with_texture(window_width, window_height, program, texture)
+        })
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, display: &Display, query: Option<&TimeElapsedQuery>) -> Result<(), Error> {
+        self.pixels.upload();
+
+        // covers the whole window in screen space, the batch matrix maps 0..2 to -1..1
+        let color = [1.0, 1.0, 1.0, 1.0];
+        self.batch.index_buffer.extend_from_slice(&[0, 1, 2, 0, 3, 2]);
+        self.batch.vertex_buffer.push(Vertex { position: [0.0, 0.0], color, style: 0, tex_coord: [0.0, 1.0], variator: 0.0 });
+        self.batch.vertex_buffer.push(Vertex { position: [2.0, 0.0], color, style: 0, tex_coord: [1.0, 1.0], variator: 0.0 });
+        self.batch.vertex_buffer.push(Vertex { position: [2.0, -2.0], color, style: 0, tex_coord: [1.0, 0.0], variator: 0.0 });
+        self.batch.vertex_buffer.push(Vertex { position: [0.0, -2.0], color, style: 0, tex_coord: [0.0, 0.0], variator: 0.0 });
+        self.batch.draw_with_params(frame, display, &profiler::timed(Batch::get_default_draw_params(), query), 0.0)
+    }
+}
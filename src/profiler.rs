@@ -0,0 +1,213 @@
+use std::{collections::VecDeque, fmt::Write as _, time::{Duration, Instant}};
+use glium::{draw_parameters::TimeElapsedQuery, Display, DrawParameters};
+use crate::{stats, CaptureError, Error, WindowHandle};
+
+// the gpu runs a frame or two behind, frames still waiting for their timer queries after this many
+// frames are given up on
+const MAX_WAITING_FRAMES: usize = 4;
+
+// how long one part of a frame took, in milliseconds
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassTiming {
+    pub window: WindowHandle,
+    pub name: String,
+    // issuing the draw calls
+    pub cpu: f32,
+    // running them, None where the gpu doesn't support timer queries
+    pub gpu: Option<f32>
+}
+
+struct Pass {
+    window: WindowHandle,
+    name: String,
+    start: Instant,
+    cpu: Duration,
+    query: Option<TimeElapsedQuery>
+}
+
+struct TraceEvent {
+    window: WindowHandle,
+    name: String,
+    gpu: bool,
+    start: Duration,
+    duration: Duration
+}
+
+struct Trace {
+    start: Instant,
+    events: Vec<TraceEvent>
+}
+
+// times the passes App::finish draws, on the cpu right away and on the gpu through timer queries that
+// are read back once they're ready, so profiling never stalls the frame
+pub(crate) struct Profiler {
+    enabled: bool,
+    // turned off for good when the gpu can't create timer queries
+    gpu_timing: bool,
+    window: WindowHandle,
+    passes: Vec<Pass>,
+    waiting: VecDeque<Vec<Pass>>,
+    latest: Vec<PassTiming>,
+    trace: Option<Trace>
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            gpu_timing: true,
+            window: WindowHandle::MAIN,
+            passes: Vec::new(),
+            waiting: VecDeque::new(),
+            latest: Vec::new(),
+            trace: None
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.passes.clear();
+            self.waiting.clear();
+            self.latest.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // the window the following passes are drawn into
+    pub fn set_window(&mut self, window: WindowHandle) {
+        self.window = window;
+    }
+
+    // draw gets the timer query to put into its draw parameters, see timed. name is only built while
+    // profiling
+    pub fn pass(&mut self, display: &Display, name: impl FnOnce() -> String, draw: impl FnOnce(Option<&TimeElapsedQuery>) -> Result<(), Error>) -> Result<(), Error> {
+        if !self.enabled {
+            return draw(None)
+        }
+        let query = match self.gpu_timing {
+            true => TimeElapsedQuery::new(display).map_err(|error| {
+                log::warn!("gpu pass timings are not available: {:?}", error);
+                self.gpu_timing = false;
+            }).ok(),
+            false => None
+        };
+
+        let draw_calls = stats::draw_calls();
+        let start = Instant::now();
+        let drawn = draw(query.as_ref());
+        let cpu = start.elapsed();
+        // a pass that drew nothing never started its query, which would then never be ready
+        if stats::draw_calls() != draw_calls {
+            self.passes.push(Pass { window: self.window, name: name(), start, cpu, query });
+        }
+        drawn
+    }
+
+    pub fn end_frame(&mut self) {
+        if !self.enabled {
+            return
+        }
+        self.waiting.push_back(std::mem::take(&mut self.passes));
+        if self.waiting.len() > MAX_WAITING_FRAMES {
+            log::debug!("gave up waiting for the gpu timings of a frame");
+            self.waiting.pop_front();
+        }
+        while let Some(passes) = self.waiting.front() {
+            if !passes.iter().all(|pass| pass.query.as_ref().is_none_or(|query| query.is_ready())) {
+                break
+            }
+            let passes = self.waiting.pop_front().unwrap();
+            self.read_frame(passes);
+        }
+    }
+
+    fn read_frame(&mut self, passes: Vec<Pass>) {
+        // the gpu runs the passes one after the other, starting no earlier than they were issued
+        let mut gpu_end: Option<Instant> = None;
+        self.latest = passes.into_iter().map(|pass| {
+            let gpu = pass.query.map(|query| Duration::from_nanos(query.get() as u64));
+            if let Some(trace) = self.trace.as_mut() {
+                let start = pass.start.saturating_duration_since(trace.start);
+                trace.events.push(TraceEvent { window: pass.window, name: pass.name.clone(), gpu: false, start, duration: pass.cpu });
+                if let Some(gpu) = gpu {
+                    let gpu_start = gpu_end.map_or(pass.start, |gpu_end| gpu_end.max(pass.start));
+                    gpu_end = Some(gpu_start + gpu);
+                    let start = gpu_start.saturating_duration_since(trace.start);
+                    trace.events.push(TraceEvent { window: pass.window, name: pass.name.clone(), gpu: true, start, duration: gpu });
+                }
+            }
+            PassTiming {
+                window: pass.window,
+                name: pass.name,
+                cpu: pass.cpu.as_secs_f32() * 1000.0,
+                gpu: gpu.map(|gpu| gpu.as_secs_f32() * 1000.0)
+            }
+        }).collect();
+    }
+
+    // the passes of the latest frame whose gpu timings have arrived
+    pub fn latest(&self) -> &[PassTiming] {
+        &self.latest
+    }
+
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace { start: Instant::now(), events: Vec::new() });
+    }
+
+    // writes the passes timed since start_trace in the chrome trace event format, for chrome://tracing
+    // or perfetto. every window is a process with a cpu and a gpu thread. returns the number of events
+    pub fn stop_trace(&mut self, path: &str) -> Result<usize, CaptureError> {
+        let trace = match self.trace.take() {
+            Some(trace) => trace,
+            None => return Ok(0)
+        };
+        let mut windows: Vec<WindowHandle> = trace.events.iter().map(|event| event.window).collect();
+        windows.sort_by_key(|window| window.0);
+        windows.dedup();
+
+        let mut lines = Vec::new();
+        for window in windows {
+            lines.push(format!(r#"{{"name":"process_name","ph":"M","pid":{},"args":{{"name":"window {}"}}}}"#, window.0, window.0));
+            lines.push(format!(r#"{{"name":"thread_name","ph":"M","pid":{},"tid":1,"args":{{"name":"cpu"}}}}"#, window.0));
+            lines.push(format!(r#"{{"name":"thread_name","ph":"M","pid":{},"tid":2,"args":{{"name":"gpu"}}}}"#, window.0));
+        }
+        for event in trace.events.iter() {
+            let (category, thread) = if event.gpu { ("gpu", 2) } else { ("cpu", 1) };
+            lines.push(format!(
+                r#"{{"name":{},"cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":{},"tid":{}}}"#,
+                json_string(&event.name), category, event.start.as_secs_f64() * 1e6, event.duration.as_secs_f64() * 1e6, event.window.0, thread
+            ));
+        }
+
+        let json = format!("{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n", lines.join(",\n"));
+        std::fs::write(path, json).map_err(|error| CaptureError::Io(path.to_string(), error))?;
+        log::info!("wrote {} trace events to '{}'", trace.events.len(), path);
+        Ok(trace.events.len())
+    }
+}
+
+// the draw parameters with the pass's timer query, if it has one
+pub(crate) fn timed<'a>(draw_params: DrawParameters<'a>, query: Option<&'a TimeElapsedQuery>) -> DrawParameters<'a> {
+    DrawParameters {
+        time_elapsed_query: query,
+        .. draw_params
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::from("\"");
+    for char in string.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            char if char.is_control() => { let _ = write!(json, "\\u{:04x}", char as u32); },
+            char => json.push(char)
+        }
+    }
+    json.push('"');
+    json
+}
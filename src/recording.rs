@@ -0,0 +1,177 @@
+use std::{fs::File, io::BufWriter, sync::mpsc::{self, SyncSender, TrySendError}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+use glium::{texture::{pixel_buffer::PixelBuffer, Texture2d}, Display, Frame, Surface};
+use image::{RgbaImage, codecs::gif::{GifEncoder, Repeat}, imageops::{self, FilterType}};
+use crate::capture::{self, CaptureError};
+
+#[derive(Clone, Debug)]
+pub enum RecordingFormat {
+    // numbered frame_00000.png files inside this directory
+    PngSequence(String),
+    Gif(String)
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordingOptions {
+    pub format: RecordingFormat,
+    // number of frames dropped between two captured ones
+    pub frame_skip: u32,
+    // 0.5 records at half the window size
+    pub scale: f32
+}
+
+impl RecordingOptions {
+    pub fn png_sequence(directory: &str) -> Self {
+        Self { format: RecordingFormat::PngSequence(directory.to_string()), frame_skip: 0, scale: 1.0 }
+    }
+
+    pub fn gif(path: &str) -> Self {
+        Self { format: RecordingFormat::Gif(path.to_string()), frame_skip: 0, scale: 1.0 }
+    }
+}
+
+// frames waiting for the worker, when it falls this far behind new frames are dropped
+const QUEUED_FRAMES: usize = 8;
+
+// a recorded frame, read back into a pixel buffer while the next frames are drawn
+enum RecordedFrame {
+    Reading(Box<PixelBuffer<(u8, u8, u8, u8)>>),
+    Read(RgbaImage)
+}
+
+impl RecordedFrame {
+    fn into_image(self) -> Result<RgbaImage, CaptureError> {
+        match self {
+            RecordedFrame::Reading(pixel_buffer) => capture::read_pixel_buffer(&pixel_buffer),
+            RecordedFrame::Read(image) => Ok(image)
+        }
+    }
+}
+
+// frames are scaled down on the gpu and read back asynchronously, encoding happens on a worker
+pub(crate) struct Recorder {
+    sender: Option<SyncSender<(RgbaImage, Duration)>>,
+    worker: JoinHandle<Result<u32, CaptureError>>,
+    frame_skip: u32,
+    frames_until_capture: u32,
+    scale: f32,
+    // the scaled copy of the frame, kept so it's only created again when the window is resized
+    texture: Option<Texture2d>,
+    // a frame is shown until the next one, so it's only sent once the next one was captured
+    pending: Option<(RecordedFrame, Instant)>
+}
+
+impl Recorder {
+    pub fn start(options: RecordingOptions) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<(RgbaImage, Duration)>(QUEUED_FRAMES);
+        let scale = options.scale.clamp(0.01, 1.0);
+
+        let worker = thread::spawn(move || {
+            let mut gif = None;
+            let mut gif_size = None;
+            if let RecordingFormat::PngSequence(directory) = &options.format {
+                std::fs::create_dir_all(directory).map_err(|error| CaptureError::Io(directory.clone(), error))?;
+            }
+            if let RecordingFormat::Gif(path) = &options.format {
+                let file = File::create(path).map_err(|error| CaptureError::Io(path.clone(), error))?;
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+                encoder.set_repeat(Repeat::Infinite).map_err(|error| CaptureError::Save(path.clone(), error))?;
+                gif = Some(encoder);
+            }
+
+            let mut frame_count = 0;
+            for (image, delay) in receiver {
+                // a gif keeps the size of its first frame, even if the window is resized while recording
+                let size = *gif_size.get_or_insert(image.dimensions());
+                let image = match gif.is_none() || size == image.dimensions() {
+                    true => image,
+                    false => imageops::resize(&image, size.0, size.1, FilterType::Triangle)
+                };
+
+                match &options.format {
+                    RecordingFormat::PngSequence(directory) => {
+                        let path = format!("{}/frame_{:05}.png", directory, frame_count);
+                        capture::save(&image, &path)?;
+                    },
+                    RecordingFormat::Gif(path) => {
+                        let frame = image::Frame::from_parts(image, 0, 0, image::Delay::from_saturating_duration(delay));
+                        gif.as_mut().unwrap().encode_frame(frame).map_err(|error| CaptureError::Save(path.clone(), error))?;
+                    }
+                }
+                frame_count += 1;
+            }
+            Ok(frame_count)
+        });
+
+        Self {
+            sender: Some(sender),
+            worker,
+            frame_skip: options.frame_skip,
+            frames_until_capture: 0,
+            scale,
+            texture: None,
+            pending: None
+        }
+    }
+
+    // called before every frame is drawn, true if it's one to capture
+    pub fn wants_frame(&mut self) -> bool {
+        if self.frames_until_capture > 0 {
+            self.frames_until_capture -= 1;
+            return false
+        }
+        self.frames_until_capture = self.frame_skip;
+        true
+    }
+
+    // called with every frame wants_frame asked for before it's finished. sends the frame before it, which
+    // was shown until now, and starts reading this one back
+    pub fn capture(&mut self, display: &Display, frame: &Frame) {
+        let sender = match self.sender.as_ref() {
+            Some(sender) => sender,
+            None => return
+        };
+        if let Some((previous, captured)) = self.pending.take() {
+            let previous = match previous.into_image() {
+                Ok(image) => image,
+                Err(error) => {
+                    log::warn!("skipped a recorded frame: {}", error);
+                    return
+                }
+            };
+            match sender.try_send((previous, captured.elapsed())) {
+                Ok(()) => {},
+                // the worker is behind, the frame before stays on screen for longer instead
+                Err(TrySendError::Full((previous, _))) => {
+                    log::debug!("the recording is behind, dropped a frame");
+                    self.pending = Some((RecordedFrame::Read(previous), captured));
+                    return
+                },
+                // the worker hung up because it failed, stop() reports why
+                Err(TrySendError::Disconnected(_)) => {
+                    log::error!("the recording stopped writing frames, stop_recording has the error");
+                    self.sender = None;
+                    return
+                }
+            }
+        }
+
+        let (width, height) = frame.get_dimensions();
+        let size = (((width as f32 * self.scale) as u32).max(1), ((height as f32 * self.scale) as u32).max(1));
+        match capture::copy_frame(display, frame, &mut self.texture, size) {
+            Ok(()) => self.pending = Some((RecordedFrame::Reading(Box::new(self.texture.as_ref().unwrap().read_to_pixel_buffer())), Instant::now())),
+            Err(error) => log::warn!("skipped a recorded frame: {}", error)
+        }
+    }
+
+    // waits for the worker to write the remaining frames and returns how many were recorded
+    pub fn stop(mut self) -> Result<u32, CaptureError> {
+        if let (Some(sender), Some((frame, captured))) = (self.sender.take(), self.pending.take()) {
+            match frame.into_image() {
+                // a failed worker has hung up, join has its error
+                Ok(image) => { let _ = sender.send((image, captured.elapsed())); },
+                Err(error) => log::warn!("skipped the last recorded frame: {}", error)
+            }
+        }
+        self.worker.join().unwrap_or(Err(CaptureError::Worker))
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+// frames are [x, y, width, height] rects in pixels of the sheet texture
+pub struct SpriteSheet {
+    path: String,
+    frames: Vec<[i32; 4]>,
+    names: HashMap<String, usize>
+}
+
+impl SpriteSheet {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            frames: Vec::new(),
+            names: HashMap::new()
+        }
+    }
+
+    // frames are numbered left to right, then top to bottom
+    pub fn from_grid(path: &str, frame_width: i32, frame_height: i32, columns: i32, rows: i32) -> Self {
+        let mut sheet = Self::new(path);
+        for row in 0 .. rows {
+            for column in 0 .. columns {
+                sheet.frames.push([column * frame_width, row * frame_height, frame_width, frame_height]);
+            }
+        }
+        sheet
+    }
+
+    pub fn add_frame(&mut self, name: &str, rect: [i32; 4]) -> usize {
+        self.frames.push(rect);
+        self.names.insert(name.to_string(), self.frames.len() - 1);
+        self.frames.len() - 1
+    }
+
+    pub fn name_frame(&mut self, name: &str, frame: usize) {
+        if frame < self.frames.len() {
+            self.names.insert(name.to_string(), frame);
+        }
+    }
+
+    pub fn frame(&self, frame: usize) -> Option<[i32; 4]> {
+        self.frames.get(frame).copied()
+    }
+
+    pub fn named(&self, name: &str) -> Option<[i32; 4]> {
+        self.names.get(name).and_then(|&frame| self.frame(frame))
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
@@ -0,0 +1,111 @@
+use std::{cell::Cell, collections::VecDeque, time::Duration};
+use crate::PassTiming;
+
+// frame times min / avg / max and the overlay graph are taken over
+pub(crate) const FRAME_HISTORY: usize = 120;
+
+// what the last finished frame cost, frame times are in milliseconds
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub fps: f32,
+    pub frame_time: f32,
+    pub frame_time_min: f32,
+    pub frame_time_avg: f32,
+    pub frame_time_max: f32,
+    pub draw_calls: u32,
+    pub vertices: u32,
+    pub texture_binds: u32,
+    pub batches: u32,
+    // empty unless profiling is on, see App::set_profiling. these are from a frame or two earlier,
+    // the gpu timings take that long to arrive
+    pub passes: Vec<PassTiming>
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct DrawCounts {
+    draw_calls: u32,
+    vertices: u32,
+    texture_binds: u32,
+    batches: u32
+}
+
+thread_local! {
+    // the batches count into this while they draw, they have no way back to the app
+    static DRAW_COUNTS: Cell<DrawCounts> = Cell::new(DrawCounts::default());
+}
+
+fn update_counts(update: impl FnOnce(&mut DrawCounts)) {
+    DRAW_COUNTS.with(|counts| {
+        let mut value = counts.get();
+        update(&mut value);
+        counts.set(value);
+    });
+}
+
+// a batch that had something to draw this frame
+pub(crate) fn count_batch() {
+    update_counts(|counts| counts.batches += 1);
+}
+
+pub(crate) fn count_draw(vertices: usize, binds_texture: bool) {
+    update_counts(|counts| {
+        counts.draw_calls += 1;
+        counts.vertices += vertices as u32;
+        counts.texture_binds += binds_texture as u32;
+    });
+}
+
+pub(crate) fn draw_calls() -> u32 {
+    DRAW_COUNTS.with(|counts| counts.get().draw_calls)
+}
+
+pub(crate) fn take_draw_counts() -> DrawCounts {
+    DRAW_COUNTS.with(|counts| counts.take())
+}
+
+pub(crate) struct StatsCollector {
+    frame_times: VecDeque<Duration>,
+    stats: FrameStats
+}
+
+impl StatsCollector {
+    pub fn new() -> Self {
+        Self {
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            stats: FrameStats::default()
+        }
+    }
+
+    pub fn end_frame(&mut self, frame_time: Duration, counts: DrawCounts, passes: &[PassTiming]) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+
+        let milliseconds = self.frame_times().map(|frame_time| frame_time.as_secs_f32() * 1000.0);
+        let (min, max, sum) = milliseconds.fold((f32::MAX, 0.0f32, 0.0), |(min, max, sum), time| (min.min(time), max.max(time), sum + time));
+        let avg = sum / self.frame_times.len() as f32;
+
+        self.stats = FrameStats {
+            fps: if avg > 0.0 { 1000.0 / avg } else { 0.0 },
+            frame_time: frame_time.as_secs_f32() * 1000.0,
+            frame_time_min: min,
+            frame_time_avg: avg,
+            frame_time_max: max,
+            draw_calls: counts.draw_calls,
+            vertices: counts.vertices,
+            texture_binds: counts.texture_binds,
+            batches: counts.batches,
+            passes: passes.to_vec()
+        };
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats.clone()
+    }
+
+    // oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.frame_times.iter().copied()
+    }
+}
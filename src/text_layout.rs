@@ -0,0 +1,323 @@
+use crate::FontHandle;
+
+// how the lines of a text sit in its box, the box is max_width wide or as wide as the longest line.
+// justify stretches the spaces of wrapped lines to fill it, the last line of a paragraph stays left
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    Justify
+}
+
+// which part of the text the y of the position is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAnchor {
+    Top,
+    Middle,
+    Bottom,
+    // the baseline of the first line
+    Baseline
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextOptions {
+    // None draws with the set_text_font font, or the built-in one
+    pub font: Option<FontHandle>,
+    // in pixels, the built-in font is size pixels per glyph
+    pub size: f32,
+    pub color: (f32, f32, f32),
+    // lines are wrapped between words to fit, words longer than a line are broken between chars
+    pub max_width: Option<f32>,
+    pub align: TextAlign,
+    pub anchor: TextAnchor,
+    // times the font's own line height
+    pub line_height: f32,
+    // extra pixels between chars, can be negative
+    pub letter_spacing: f32
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            font: None,
+            size: 16.0,
+            color: (1.0, 1.0, 1.0),
+            max_width: None,
+            align: TextAlign::Left,
+            anchor: TextAnchor::Top,
+            line_height: 1.0,
+            letter_spacing: 0.0
+        }
+    }
+}
+
+// a glyph to draw, in the font's atlas and on the screen
+pub(crate) struct GlyphQuad {
+    // which of the font's atlas textures src_rect is in
+    pub page: usize,
+    pub src_rect: [i32; 4],
+    pub dst_rect: [i32; 4]
+}
+
+// what layout needs from a font, in pixels at the given size
+pub(crate) trait Glyphs {
+    // how far the top of the tallest glyphs is above the baseline
+    fn ascent(&self, size: f32) -> f32;
+    fn line_height(&self, size: f32) -> f32;
+    fn advance(&self, char: char, size: f32) -> f32;
+    fn kerning(&self, previous: char, char: char, size: f32) -> f32;
+    // the glyph with the pen at pen on the baseline, None for glyphs without pixels
+    fn quad(&mut self, char: char, pen: [f32; 2], size: f32) -> Option<GlyphQuad>;
+}
+
+struct Line {
+    chars: Vec<char>,
+    width: f32,
+    // lines that were wrapped, the only ones justify stretches
+    wrapped: bool
+}
+
+// a string broken into lines and placed, ready to be measured or turned into quads
+pub(crate) struct TextBlock {
+    lines: Vec<Line>,
+    // top left corner of the box
+    origin: [f32; 2],
+    width: f32,
+    height: f32,
+    ascent: f32,
+    line_advance: f32
+}
+
+impl TextBlock {
+    pub fn new(glyphs: &impl Glyphs, string: &str, position: [i32; 2], options: &TextOptions) -> Self {
+        let mut lines = Vec::new();
+        for paragraph in string.split('\n') {
+            wrap_paragraph(glyphs, paragraph, options, &mut lines);
+        }
+
+        let width = options.max_width.unwrap_or_else(|| lines.iter().map(|line| line.width).fold(0.0, f32::max));
+        let ascent = glyphs.ascent(options.size);
+        let line_advance = glyphs.line_height(options.size) * options.line_height;
+        let height = lines.len() as f32 * line_advance;
+        let top = match options.anchor {
+            TextAnchor::Top => position[1] as f32,
+            TextAnchor::Middle => position[1] as f32 - height / 2.0,
+            TextAnchor::Bottom => position[1] as f32 - height,
+            TextAnchor::Baseline => position[1] as f32 - ascent
+        };
+        Self { lines, origin: [position[0] as f32, top], width, height, ascent, line_advance }
+    }
+
+    // [x, y, width, height] of the box the text is laid out in
+    pub fn bounds(&self) -> [i32; 4] {
+        [self.origin[0].round() as i32, self.origin[1].round() as i32, self.width.round() as i32, self.height.round() as i32]
+    }
+
+    pub fn quads(&self, glyphs: &mut impl Glyphs, options: &TextOptions) -> Vec<GlyphQuad> {
+        let mut quads = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            let free_space = self.width - line.width;
+            let spaces = line.chars.iter().filter(|char| **char == ' ').count();
+            let (offset, space_stretch) = match options.align {
+                TextAlign::Left => (0.0, 0.0),
+                TextAlign::Center => (free_space / 2.0, 0.0),
+                TextAlign::Right => (free_space, 0.0),
+                TextAlign::Justify if line.wrapped && spaces > 0 => (0.0, free_space / spaces as f32),
+                TextAlign::Justify => (0.0, 0.0)
+            };
+
+            let mut pen = [self.origin[0] + offset, self.origin[1] + self.ascent + index as f32 * self.line_advance];
+            let mut previous = None;
+            for char in line.chars.iter().copied() {
+                if let Some(previous) = previous {
+                    pen[0] += glyphs.kerning(previous, char, options.size) + options.letter_spacing;
+                }
+                quads.extend(glyphs.quad(char, pen, options.size));
+                pen[0] += glyphs.advance(char, options.size);
+                if char == ' ' {
+                    pen[0] += space_stretch;
+                }
+                previous = Some(char);
+            }
+        }
+        quads
+    }
+}
+
+// what char adds to the width of a line that ends in previous
+fn char_advance(glyphs: &impl Glyphs, previous: Option<char>, char: char, options: &TextOptions) -> f32 {
+    let kerning = previous.map_or(0.0, |previous| glyphs.kerning(previous, char, options.size) + options.letter_spacing);
+    kerning + glyphs.advance(char, options.size)
+}
+
+fn line_width(glyphs: &impl Glyphs, chars: &[char], options: &TextOptions) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for char in chars.iter().copied() {
+        width += char_advance(glyphs, previous, char, options);
+        previous = Some(char);
+    }
+    width
+}
+
+// greedy, a line takes words until the next one doesn't fit. the space a line is broken at is dropped.
+// the line's width is kept up to date as words are added, so every char is measured once
+fn wrap_paragraph(glyphs: &impl Glyphs, paragraph: &str, options: &TextOptions, lines: &mut Vec<Line>) {
+    let max_width = match options.max_width {
+        Some(max_width) => max_width,
+        None => {
+            let chars: Vec<char> = paragraph.chars().collect();
+            lines.push(Line { width: line_width(glyphs, &chars, options), chars, wrapped: false });
+            return
+        }
+    };
+
+    let mut line = Line { chars: Vec::new(), width: 0.0, wrapped: false };
+    for word in paragraph.split(' ') {
+        // the word's width after a space at the end of the line, and on a line of its own
+        let mut previous = line.chars.last().copied();
+        let mut joined = 0.0;
+        if previous.is_some() {
+            joined += char_advance(glyphs, previous, ' ', options);
+            previous = Some(' ');
+        }
+        let mut alone = 0.0;
+        for (index, char) in word.chars().enumerate() {
+            let advance = char_advance(glyphs, previous, char, options);
+            joined += advance;
+            alone += match index {
+                0 => char_advance(glyphs, None, char, options),
+                _ => advance
+            };
+            previous = Some(char);
+        }
+
+        if line.chars.is_empty() || line.width + joined <= max_width {
+            if !line.chars.is_empty() {
+                line.chars.push(' ');
+            }
+            line.chars.extend(word.chars());
+            line.width += joined;
+        } else {
+            let full_line = std::mem::replace(&mut line, Line { chars: word.chars().collect(), width: alone, wrapped: false });
+            lines.push(Line { wrapped: true, ..full_line });
+        }
+
+        if line.chars.len() > 1 && line.width > max_width {
+            break_line(glyphs, &mut line, max_width, options, lines);
+        }
+    }
+    lines.push(line);
+}
+
+// a line too long to fit is broken between chars, every full piece is pushed and the rest stays in line
+fn break_line(glyphs: &impl Glyphs, line: &mut Line, max_width: f32, options: &TextOptions, lines: &mut Vec<Line>) {
+    let chars = std::mem::take(&mut line.chars);
+    line.width = 0.0;
+    for char in chars {
+        let advance = char_advance(glyphs, line.chars.last().copied(), char, options);
+        if !line.chars.is_empty() && line.width + advance > max_width {
+            let piece = std::mem::replace(line, Line { chars: vec![char], width: glyphs.advance(char, options.size), wrapped: false });
+            lines.push(piece);
+        } else {
+            line.chars.push(char);
+            line.width += advance;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every char is 10 pixels wide, whatever the size
+    struct FixedGlyphs;
+
+    impl Glyphs for FixedGlyphs {
+        fn ascent(&self, _size: f32) -> f32 {
+            8.0
+        }
+
+        fn line_height(&self, _size: f32) -> f32 {
+            12.0
+        }
+
+        fn advance(&self, _char: char, _size: f32) -> f32 {
+            10.0
+        }
+
+        fn kerning(&self, _previous: char, _char: char, _size: f32) -> f32 {
+            0.0
+        }
+
+        fn quad(&mut self, char: char, pen: [f32; 2], _size: f32) -> Option<GlyphQuad> {
+            if char == ' ' {
+                return None
+            }
+            Some(GlyphQuad { page: 0, src_rect: [0, 0, 10, 10], dst_rect: [pen[0] as i32, pen[1] as i32 - 8, 10, 10] })
+        }
+    }
+
+    fn options(max_width: Option<f32>) -> TextOptions {
+        TextOptions { max_width, ..Default::default() }
+    }
+
+    fn lines(block: &TextBlock) -> Vec<(String, bool)> {
+        block.lines.iter().map(|line| (line.chars.iter().collect(), line.wrapped)).collect()
+    }
+
+    #[test]
+    fn wraps_greedily_between_words() {
+        let block = TextBlock::new(&FixedGlyphs, "the quick brown fox jumps over", [0, 0], &options(Some(100.0)));
+        assert_eq!(lines(&block), [
+            ("the quick".to_string(), true),
+            ("brown fox".to_string(), true),
+            ("jumps over".to_string(), false)
+        ]);
+        assert_eq!(block.lines[0].width, 90.0);
+    }
+
+    #[test]
+    fn breaks_words_longer_than_a_line() {
+        let block = TextBlock::new(&FixedGlyphs, "ab abcdefghijklmnopqrstuvwxy yz", [0, 0], &options(Some(100.0)));
+        assert_eq!(lines(&block), [
+            ("ab".to_string(), true),
+            ("abcdefghij".to_string(), false),
+            ("klmnopqrst".to_string(), false),
+            ("uvwxy yz".to_string(), false)
+        ]);
+        assert_eq!(block.lines[3].width, 80.0);
+    }
+
+    #[test]
+    fn justify_leaves_the_last_line_left() {
+        let options = TextOptions { align: TextAlign::Justify, ..options(Some(70.0)) };
+        let block = TextBlock::new(&FixedGlyphs, "aa bb cc", [0, 0], &options);
+        let xs: Vec<i32> = block.quads(&mut FixedGlyphs, &options).iter().map(|quad| quad.dst_rect[0]).collect();
+        // the 20 pixels left on the first line go into its space
+        assert_eq!(xs, [0, 10, 50, 60, 0, 10]);
+    }
+
+    #[test]
+    fn anchors_move_the_box() {
+        let top = |anchor| {
+            let options = TextOptions { anchor, ..options(None) };
+            TextBlock::new(&FixedGlyphs, "one\ntwo", [0, 100], &options).bounds()[1]
+        };
+        assert_eq!(top(TextAnchor::Top), 100);
+        assert_eq!(top(TextAnchor::Middle), 88);
+        assert_eq!(top(TextAnchor::Bottom), 76);
+        assert_eq!(top(TextAnchor::Baseline), 92);
+    }
+
+    #[test]
+    fn letter_spacing_goes_between_chars() {
+        let options = TextOptions { letter_spacing: 2.0, ..options(None) };
+        assert_eq!(TextBlock::new(&FixedGlyphs, "abcd", [0, 0], &options).bounds(), [0, 0, 46, 12]);
+
+        // and counts when wrapping, "ab cd" would be 58 wide
+        let options = TextOptions { max_width: Some(55.0), ..options };
+        assert_eq!(lines(&TextBlock::new(&FixedGlyphs, "ab cd", [0, 0], &options)).len(), 2);
+    }
+}
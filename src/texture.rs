@@ -0,0 +1,207 @@
+use std::cell::Cell;
+use glium::uniforms::{SamplerBehavior, SamplerWrapFunction, MinifySamplerFilter, MagnifySamplerFilter};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Nearest,
+    Linear
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    Clamp,
+    Repeat,
+    Mirror
+}
+
+impl Wrap {
+    fn to_glium(self) -> SamplerWrapFunction {
+        match self {
+            Wrap::Clamp => SamplerWrapFunction::Clamp,
+            Wrap::Repeat => SamplerWrapFunction::Repeat,
+            Wrap::Mirror => SamplerWrapFunction::Mirror
+        }
+    }
+}
+
+// mipmaps are generated on upload (except for dynamic textures), `mipmaps` decides whether minifying samples them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextureOptions {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmaps: bool,
+    pub wrap: Wrap
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Nearest,
+            mipmaps: false,
+            wrap: Wrap::Mirror
+        }
+    }
+}
+
+impl TextureOptions {
+    pub(crate) fn sampler_behavior(&self, wrap: Wrap) -> SamplerBehavior {
+        let minify_filter = match (self.min_filter, self.mipmaps) {
+            (Filter::Nearest, false) => MinifySamplerFilter::Nearest,
+            (Filter::Linear, false) => MinifySamplerFilter::Linear,
+            (Filter::Nearest, true) => MinifySamplerFilter::NearestMipmapNearest,
+            (Filter::Linear, true) => MinifySamplerFilter::LinearMipmapLinear
+        };
+        let magnify_filter = match self.mag_filter {
+            Filter::Nearest => MagnifySamplerFilter::Nearest,
+            Filter::Linear => MagnifySamplerFilter::Linear
+        };
+        SamplerBehavior {
+            minify_filter,
+            magnify_filter,
+            wrap_function: (wrap.to_glium(), wrap.to_glium(), wrap.to_glium()),
+            ..Default::default()
+        }
+    }
+}
+
+// a loaded texture shared between every batch that draws it
+pub(crate) struct Texture {
+    pub texture: glium::texture::SrgbTexture2d,
+    pub options: Cell<TextureOptions>,
+    // the file it was loaded from, used for hot reloading
+    pub path: Option<String>
+}
+
+impl Texture {
+    pub fn new(texture: glium::texture::SrgbTexture2d) -> Self {
+        Self { texture, options: Cell::new(TextureOptions::default()), path: None }
+    }
+
+    pub fn from_file(texture: glium::texture::SrgbTexture2d, path: &str) -> Self {
+        Self { texture, options: Cell::new(TextureOptions::default()), path: Some(path.to_string()) }
+    }
+
+    pub fn sampler_behavior(&self) -> SamplerBehavior {
+        self.wrapped_sampler_behavior(self.options.get().wrap)
+    }
+
+    // sampling mipmaps that were never generated would give a black texture
+    pub fn wrapped_sampler_behavior(&self, wrap: Wrap) -> SamplerBehavior {
+        let mut options = self.options.get();
+        options.mipmaps &= self.texture.get_mipmap_levels() > 1;
+        options.sampler_behavior(wrap)
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(String, std::io::Error),
+    Decode(String, image::ImageError),
+    Upload(String, glium::texture::TextureCreationError),
+    // a dynamic texture that's empty or too big to hold in memory, width and height
+    Size(String, u32, u32),
+    // a pixel buffer that doesn't match the size written, expected and actual length in bytes
+    Length(String, usize, usize)
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::Io(name, error) => write!(f, "could not read texture '{}': {}", name, error),
+            TextureError::Decode(name, error) => write!(f, "could not decode texture '{}': {}", name, error),
+            TextureError::Upload(name, error) => write!(f, "could not upload texture '{}' to the gpu: {}", name, error),
+            TextureError::Size(name, width, height) => write!(f, "texture '{}' can't be {}x{} pixels", name, width, height),
+            TextureError::Length(name, expected, actual) => write!(f, "expected {} bytes of rgba pixels for texture '{}', got {}", expected, name, actual)
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Io(_, error) => Some(error),
+            TextureError::Decode(_, error) => Some(error),
+            TextureError::Upload(_, error) => Some(error),
+            TextureError::Size(_, _, _) | TextureError::Length(_, _, _) => None
+        }
+    }
+}
+
+// the format is guessed from the file contents, so a .png that is really a jpeg still loads. formats
+// without a signature, like tga, go by the extension
+pub(crate) fn decode_file(path: &str) -> Result<image::RgbaImage, TextureError> {
+    let reader = image::io::Reader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|error| TextureError::Io(path.to_string(), error))?;
+    decode(path, reader)
+}
+
+// like decode_file, with the extension of name as the fallback format
+pub(crate) fn decode_bytes(name: &str, bytes: &[u8]) -> Result<image::RgbaImage, TextureError> {
+    let mut reader = image::io::Reader::new(std::io::Cursor::new(bytes));
+    if let Ok(format) = image::ImageFormat::from_path(name) {
+        reader.set_format(format);
+    }
+    let reader = reader.with_guessed_format().map_err(|error| TextureError::Io(name.to_string(), error))?;
+    decode(name, reader)
+}
+
+fn decode<R: std::io::BufRead + std::io::Seek>(name: &str, reader: image::io::Reader<R>) -> Result<image::RgbaImage, TextureError> {
+    reader.decode()
+        .map(|image| image.to_rgba8())
+        .map_err(|error| TextureError::Decode(name.to_string(), error))
+}
+
+pub(crate) fn upload(display: &glium::Display, name: &str, image: &image::RgbaImage) -> Result<glium::texture::SrgbTexture2d, TextureError> {
+    let image_dimensions = image.dimensions();
+    log::debug!("uploading texture '{}' ({}x{})", name, image_dimensions.0, image_dimensions.1);
+    let image = glium::texture::RawImage2d::from_raw_rgba_reversed(image.as_raw(), image_dimensions);
+    glium::texture::SrgbTexture2d::new(display, image).map_err(|error| TextureError::Upload(name.to_string(), error))
+}
+
+// dynamic textures skip mipmaps, they would go stale after every write
+pub(crate) fn upload_dynamic(display: &glium::Display, name: &str, image: &image::RgbaImage) -> Result<glium::texture::SrgbTexture2d, TextureError> {
+    let image_dimensions = image.dimensions();
+    log::debug!("uploading dynamic texture '{}' ({}x{})", name, image_dimensions.0, image_dimensions.1);
+    let image = glium::texture::RawImage2d::from_raw_rgba_reversed(image.as_raw(), image_dimensions);
+    glium::texture::SrgbTexture2d::with_mipmaps(display, image, glium::texture::MipmapsOption::NoMipmap).map_err(|error| TextureError::Upload(name.to_string(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x1 uncompressed tga, a red and a half transparent blue pixel stored as bgra from the top left
+    const TINY_TGA: [u8; 26] = [
+        0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 32, 0x28,
+        0, 0, 255, 255,
+        255, 0, 0, 128
+    ];
+
+    #[test]
+    fn decodes_tga_bytes_by_their_name() {
+        let image = decode_bytes("tiny.tga", &TINY_TGA).unwrap();
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn decodes_tga_files_by_their_extension() {
+        let path = std::env::temp_dir().join(format!("blithaven_tiny_{}.tga", std::process::id()));
+        std::fs::write(&path, TINY_TGA).unwrap();
+        let image = decode_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(image.unwrap().get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn tga_without_a_hint_is_an_error() {
+        assert!(matches!(decode_bytes("tiny", &TINY_TGA), Err(TextureError::Decode(_, _))));
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+pub(crate) const DEFAULT_TARGET_FPS: u32 = 60;
+// steps past this in one frame are dropped, so a long stall doesn't snowball into ever longer frames
+const MAX_FIXED_STEPS: u32 = 8;
+
+pub(crate) struct FixedUpdate {
+    pub step: Duration,
+    pub callback: Box<dyn FnMut(f32)>
+}
+
+// when frames start, how long the last one took and how much time the fixed update still owes
+pub(crate) struct FrameClock {
+    start: Instant,
+    frame_start: Instant,
+    delta: Duration,
+    accumulator: Duration,
+    pub target_frame_time: Option<Duration>,
+    // kept apart from the callback, which App::begin_frame takes out while it runs
+    fixed_step: Option<Duration>,
+    pub fixed_update: Option<Box<dyn FnMut(f32)>>,
+    // counts set_fixed_update calls, so a callback that was replaced or cleared while it ran isn't put back
+    pub fixed_generation: u64
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            frame_start: now,
+            delta: Duration::ZERO,
+            accumulator: Duration::ZERO,
+            target_frame_time: frame_time(DEFAULT_TARGET_FPS),
+            fixed_step: None,
+            fixed_update: None,
+            fixed_generation: 0
+        }
+    }
+
+    // starts a frame, returns how many fixed steps are due before it's drawn
+    pub fn tick(&mut self) -> u32 {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&mut self, now: Instant) -> u32 {
+        self.delta = now - self.frame_start;
+        self.frame_start = now;
+
+        let step = match self.fixed_step {
+            Some(step) => step,
+            None => return 0
+        };
+        self.accumulator += self.delta;
+        let mut steps = 0;
+        while self.accumulator >= step && steps < MAX_FIXED_STEPS {
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if steps == MAX_FIXED_STEPS {
+            self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % step.as_nanos()) as u64);
+        }
+        steps
+    }
+
+    pub fn set_fixed_update(&mut self, fixed_update: Option<FixedUpdate>) {
+        self.fixed_step = fixed_update.as_ref().map(|fixed_update| fixed_update.step);
+        self.fixed_update = fixed_update.map(|fixed_update| fixed_update.callback);
+        self.fixed_generation += 1;
+        self.accumulator = Duration::ZERO;
+    }
+
+    pub fn fixed_step(&self) -> Option<Duration> {
+        self.fixed_step
+    }
+
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.frame_start - self.start
+    }
+
+    // how far the frame is between the last fixed step and the next one, 1.0 without a fixed update
+    pub fn alpha(&self) -> f32 {
+        match self.fixed_step {
+            Some(step) => self.accumulator.as_secs_f32() / step.as_secs_f32(),
+            None => 1.0
+        }
+    }
+}
+
+// 0 means no limit
+pub(crate) fn frame_time(fps: u32) -> Option<Duration> {
+    match fps {
+        0 => None,
+        fps => Some(Duration::from_secs(1) / fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_with_step(step: Duration) -> FrameClock {
+        let mut clock = FrameClock::new();
+        clock.set_fixed_update(Some(FixedUpdate { step, callback: Box::new(|_| ()) }));
+        clock
+    }
+
+    #[test]
+    fn accumulates_time_into_steps() {
+        let step = Duration::from_millis(10);
+        let mut clock = clock_with_step(step);
+        let start = clock.frame_start;
+        assert_eq!(clock.tick_at(start + Duration::from_millis(5)), 0);
+        assert_eq!(clock.tick_at(start + Duration::from_millis(25)), 2);
+        assert_eq!(clock.accumulator, Duration::from_millis(5));
+        assert_eq!(clock.tick_at(start + Duration::from_millis(30)), 1);
+        assert_eq!(clock.accumulator, Duration::ZERO);
+    }
+
+    #[test]
+    fn clamps_steps_after_a_stall() {
+        let step = Duration::from_millis(10);
+        let mut clock = clock_with_step(step);
+        let start = clock.frame_start;
+        assert_eq!(clock.tick_at(start + Duration::from_millis(1003)), MAX_FIXED_STEPS);
+        // the whole steps that didn't fit are dropped, only the part of a step is kept
+        assert_eq!(clock.accumulator, Duration::from_millis(3));
+        assert_eq!(clock.tick_at(start + Duration::from_millis(1010)), 1);
+    }
+
+    #[test]
+    fn alpha_is_the_part_of_a_step_left() {
+        let mut clock = clock_with_step(Duration::from_millis(20));
+        let start = clock.frame_start;
+        clock.tick_at(start + Duration::from_millis(25));
+        assert!((clock.alpha() - 0.25).abs() < 1e-6);
+        // the callback is taken out while it runs, the step is still known
+        let callback = clock.fixed_update.take();
+        assert!((clock.alpha() - 0.25).abs() < 1e-6);
+        clock.fixed_update = callback;
+
+        clock.set_fixed_update(None);
+        assert_eq!(clock.alpha(), 1.0);
+        assert_eq!(clock.tick_at(start + Duration::from_millis(100)), 0);
+    }
+
+    #[test]
+    fn counts_fixed_update_changes() {
+        let mut clock = clock_with_step(Duration::from_millis(10));
+        let generation = clock.fixed_generation;
+        clock.set_fixed_update(None);
+        assert_ne!(clock.fixed_generation, generation);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use crate::{DynamicTexture, TextureError, TextureHandle};
+use crate::text_layout::{GlyphQuad, Glyphs};
+
+// every font gets an atlas this big, glyphs are rasterized into it the first time they're drawn at a size
+pub(crate) const ATLAS_SIZE: u32 = 1024;
+// a full atlas gets another page up to this many, after that they're all cleared once the frame is drawn
+pub(crate) const MAX_ATLAS_PAGES: usize = 4;
+// between glyphs in the atlas, so filtering doesn't pick up the neighbours
+const GLYPH_PADDING: u32 = 1;
+
+// a font loaded with App::load_font, draw with it through draw_text
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FontHandle(pub(crate) usize);
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(String, std::io::Error),
+    Parse(String, &'static str),
+    Atlas(String, TextureError)
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io(name, error) => write!(f, "could not read font '{}': {}", name, error),
+            FontError::Parse(name, error) => write!(f, "could not parse font '{}': {}", name, error),
+            FontError::Atlas(name, error) => write!(f, "could not create the glyph atlas for font '{}': {}", name, error)
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(_, error) => Some(error),
+            FontError::Parse(_, _) => None,
+            FontError::Atlas(_, error) => Some(error)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CachedGlyph {
+    page: usize,
+    // None for glyphs without pixels, like spaces
+    src_rect: Option<[i32; 4]>,
+    // from the pen position on the baseline to the top left of the bitmap
+    offset: [f32; 2]
+}
+
+pub(crate) struct TrueTypeFont {
+    pub name: String,
+    font: fontdue::Font,
+    pages: Vec<DynamicTexture>,
+    // keyed by glyph index and the bits of the pixel size
+    glyphs: HashMap<(u16, u32), CachedGlyph>,
+    // the page being filled, in rows left to right, top to bottom
+    page: usize,
+    cursor: [u32; 2],
+    row_height: u32,
+    // a glyph didn't fit on the last page, it's left out until there's room
+    full: bool
+}
+
+impl TrueTypeFont {
+    pub fn new(name: &str, font: fontdue::Font, atlas: DynamicTexture) -> Self {
+        Self { name: name.to_string(), font, pages: vec![atlas], glyphs: HashMap::new(), page: 0, cursor: [0, 0], row_height: 0, full: false }
+    }
+
+    pub fn texture(&self, page: usize) -> TextureHandle {
+        self.pages[page].handle()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    // true when glyphs were left out and another page would take them, see App::truetype_quads
+    pub fn needs_page(&self) -> bool {
+        self.full && self.pages.len() < MAX_ATLAS_PAGES
+    }
+
+    pub fn add_page(&mut self, page: DynamicTexture) {
+        self.pages.push(page);
+        self.next_page();
+    }
+
+    pub fn upload(&mut self) {
+        for page in self.pages.iter_mut() {
+            page.upload();
+        }
+    }
+
+    // called once the frame is drawn, when every page is full they're cleared so the glyphs left out
+    // get rasterized next frame. quads drawn this frame still pointed at the old glyphs
+    pub fn end_frame(&mut self) {
+        if !self.full {
+            return
+        }
+        log::debug!("the glyph atlas of font '{}' is full, starting over", self.name);
+        self.glyphs.clear();
+        for page in self.pages.iter_mut() {
+            page.fill([0, 0, 0, 0]);
+        }
+        self.page = 0;
+        self.cursor = [0, 0];
+        self.row_height = 0;
+        self.full = false;
+    }
+
+    fn next_page(&mut self) {
+        self.page += 1;
+        self.cursor = [0, 0];
+        self.row_height = 0;
+        self.full = false;
+    }
+
+    // None when the glyph doesn't fit into the atlas right now
+    fn glyph(&mut self, index: u16, size: f32) -> Option<CachedGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(index, size.to_bits())) {
+            return Some(*glyph)
+        }
+        let (metrics, coverage) = self.font.rasterize_indexed(index, size);
+        let src_rect = match (metrics.width as u32, metrics.height as u32) {
+            (0, _) | (_, 0) => None,
+            (width, height) => self.pack(width, height)?.and_then(|[x, y]| {
+                let pixels: Vec<u8> = coverage.iter().flat_map(|alpha| [255, 255, 255, *alpha]).collect();
+                match self.pages[self.page].write_region(x, y, width, height, &pixels) {
+                    Ok(()) => Some([x as i32, y as i32, width as i32, height as i32]),
+                    Err(error) => {
+                        log::warn!("{}", error);
+                        None
+                    }
+                }
+            })
+        };
+        let glyph = CachedGlyph {
+            page: self.page,
+            src_rect,
+            offset: [metrics.xmin as f32, -(metrics.ymin as f32 + metrics.height as f32)]
+        };
+        self.glyphs.insert((index, size.to_bits()), glyph);
+        Some(glyph)
+    }
+
+    // where on the current page a width * height glyph goes, Some(None) for glyphs that never fit and
+    // None when every page is full. nothing already on a page is moved or cleared until end_frame
+    fn pack(&mut self, width: u32, height: u32) -> Option<Option<[u32; 2]>> {
+        if width + GLYPH_PADDING > ATLAS_SIZE || height + GLYPH_PADDING > ATLAS_SIZE {
+            log::warn!("a glyph of font '{}' is too big for its atlas ({}x{})", self.name, width, height);
+            return Some(None)
+        }
+        if self.cursor[0] + width + GLYPH_PADDING > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + height + GLYPH_PADDING > ATLAS_SIZE {
+            // pages cleared by end_frame are filled again before new ones are added
+            if self.page + 1 == self.pages.len() {
+                self.full = true;
+                return None
+            }
+            self.next_page();
+        }
+        let position = self.cursor;
+        self.cursor[0] += width + GLYPH_PADDING;
+        self.row_height = self.row_height.max(height + GLYPH_PADDING);
+        Some(Some(position))
+    }
+}
+
+impl Glyphs for TrueTypeFont {
+    fn ascent(&self, size: f32) -> f32 {
+        self.font.horizontal_line_metrics(size).map_or(size, |metrics| metrics.ascent)
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        self.font.horizontal_line_metrics(size).map_or(size, |metrics| metrics.new_line_size)
+    }
+
+    fn advance(&self, char: char, size: f32) -> f32 {
+        self.font.metrics(char, size).advance_width
+    }
+
+    fn kerning(&self, previous: char, char: char, size: f32) -> f32 {
+        self.font.horizontal_kern(previous, char, size).unwrap_or(0.0)
+    }
+
+    fn quad(&mut self, char: char, pen: [f32; 2], size: f32) -> Option<GlyphQuad> {
+        let glyph = self.glyph(self.font.lookup_glyph_index(char), size)?;
+        let src_rect = glyph.src_rect?;
+        let [x, y] = [(pen[0] + glyph.offset[0]).round() as i32, (pen[1] + glyph.offset[1]).round() as i32];
+        Some(GlyphQuad { page: glyph.page, src_rect, dst_rect: [x, y, src_rect[2], src_rect[3]] })
+    }
+}
@@ -0,0 +1,314 @@
+use std::rc::Rc;
+use glium::{glutin::{event::{ElementState, MouseButton, VirtualKeyCode, WindowEvent}, window::WindowId}, texture::Texture2d, Display, Frame, Surface};
+use crate::{Batch, BatchMap, DrawTarget, Error, InstanceBatch, Instances, MaskLayer, Options, Programs, SpriteOptions, TextureBatch, TextureHandle, WindowConfig, WindowMode};
+use crate::assets::Assets;
+use crate::capture::Captures;
+use crate::profiler::{timed, Profiler};
+use crate::texture::Texture;
+
+// draw calls go to one window at a time, see App::set_target_window. the window opened by initialize
+// is WindowHandle::MAIN
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WindowHandle(pub(crate) usize);
+
+impl WindowHandle {
+    pub const MAIN: WindowHandle = WindowHandle(0);
+}
+
+// drawn over everything at the mouse position while the os cursor is hidden
+pub(crate) struct CursorImage {
+    pub texture: TextureHandle,
+    pub size: [i32; 2],
+    pub hotspot: [i32; 2],
+    pub batch: TextureBatch
+}
+
+// what's drawn outside of masks. the base is split at every mask layer, base layer i is drawn before mask
+// layer i, so content keeps the order it was drawn in
+pub(crate) struct BaseLayer {
+    pub batch: Batch,
+    pub texture_batches: BatchMap<(TextureHandle, bool), TextureBatch>,
+    pub instances: Instances
+}
+
+impl BaseLayer {
+    fn new(window_width: i32, window_height: i32, programs: &Programs, instance_batch: InstanceBatch) -> Self {
+        Self {
+            batch: Batch::new(window_width, window_height, programs.shape.clone()),
+            texture_batches: BatchMap::new(),
+            instances: Instances::new(instance_batch)
+        }
+    }
+
+    fn resize(&mut self, window_width: i32, window_height: i32) {
+        self.batch.resize(window_width, window_height);
+        for texture_batch in self.texture_batches.iter_mut() {
+            texture_batch.resize(window_width, window_height);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, display: &Display, window_size: [f32; 2], profiler: &mut Profiler) -> Result<(), Error> {
+        profiler.pass(display, || "shapes".to_string(), |query| {
+            self.batch.draw_with_params(frame, display, &timed(Batch::get_default_draw_params(), query))
+        })?;
+        for texture_batch in self.texture_batches.iter_mut() {
+            let texture = texture_batch.texture.clone();
+            profiler.pass(display, || texture_pass_name(&texture), |query| {
+                texture_batch.draw_with_params(frame, display, &timed(Batch::get_default_draw_params(), query), 0.0)
+            })?;
+        }
+        profiler.pass(display, || "instances".to_string(), |query| {
+            self.instances.shapes.draw(frame, display, window_size, &timed(Batch::get_default_draw_params(), query))
+        })?;
+        for instance_batch in self.instances.sprites.iter_mut() {
+            let texture = instance_batch.texture.clone();
+            profiler.pass(display, || format!("instanced {}", texture.as_deref().map_or("texture".to_string(), texture_pass_name)), |query| {
+                instance_batch.draw(frame, display, window_size, &timed(Batch::get_default_draw_params(), query))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+// one window with its own gl context, the batches drawn into it this frame and its input
+pub(crate) struct WindowState {
+    pub id: WindowId,
+    pub display: Display,
+    pub options: Options,
+    pub base_layers: Vec<BaseLayer>,
+    pub mask_layers: Vec<MaskLayer>,
+    pub used_mask_layers: usize,
+    pub draw_target: DrawTarget,
+    pub physical_size: [u32; 2],
+    pub scale_factor: f64,
+    pub transparent: bool,
+    pub window_mode: WindowMode,
+    pub focused: bool,
+    pub minimized: bool,
+    pub cursor_position: [f64; 2],
+    pub cursor_image: Option<CursorImage>,
+    // what happened to this window during the last frame
+    pub events: Vec<WindowEvent<'static>>,
+    pub pressed_keys: Vec<VirtualKeyCode>,
+    pub pressed_buttons: Vec<MouseButton>,
+    // the copy of the frame made before it was finished, only while a screenshot or recording needs it.
+    // the texture is kept for the next one
+    pub last_frame: Option<Texture2d>,
+    pub captures: Captures,
+    pub capture_frame: bool
+}
+
+impl WindowState {
+    pub fn new(display: Display, config: &WindowConfig, programs: &Programs, instance_batch: InstanceBatch, scale_factor_override: Option<f64>) -> Self {
+        let id = display.gl_window().window().id();
+        let scale_factor = display.gl_window().window().scale_factor();
+        let (physical_width, physical_height) = display.get_framebuffer_dimensions();
+        let [window_width, window_height] = crate::logical_size([physical_width, physical_height], scale_factor_override.unwrap_or(scale_factor));
+
+        Self {
+            id,
+            display,
+            options: Options::new(window_width, window_height),
+            base_layers: vec![BaseLayer::new(window_width, window_height, programs, instance_batch)],
+            mask_layers: Vec::new(),
+            used_mask_layers: 0,
+            draw_target: DrawTarget::Base,
+            physical_size: [physical_width, physical_height],
+            scale_factor,
+            transparent: config.transparent,
+            window_mode: config.mode,
+            focused: true,
+            minimized: false,
+            cursor_position: [0.0, 0.0],
+            cursor_image: None,
+            events: Vec::new(),
+            pressed_keys: Vec::new(),
+            pressed_buttons: Vec::new(),
+            last_frame: None,
+            captures: Captures::default(),
+            capture_frame: false
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent<'static>, scale_factor_override: Option<f64>) {
+        match event {
+            WindowEvent::Resized(size) => {
+                // some platforms report minimizing as a resize to nothing
+                self.minimized = size.width == 0 || size.height == 0;
+                if !self.minimized {
+                    self.resize([size.width, size.height], scale_factor_override);
+                }
+            },
+            WindowEvent::Focused(focused) => self.focused = *focused,
+            WindowEvent::CursorMoved { position, .. } => self.cursor_position = [position.x, position.y],
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode {
+                    self.pressed_keys.retain(|pressed| *pressed != key);
+                    if input.state == ElementState::Pressed {
+                        self.pressed_keys.push(key);
+                    }
+                }
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.pressed_buttons.retain(|pressed| pressed != button);
+                if *state == ElementState::Pressed {
+                    self.pressed_buttons.push(*button);
+                }
+            },
+            _ => ()
+        }
+        self.events.push(event.clone());
+    }
+
+    pub fn scale_factor(&self, scale_factor_override: Option<f64>) -> f64 {
+        scale_factor_override.unwrap_or(self.scale_factor)
+    }
+
+    pub fn resize(&mut self, physical_size: [u32; 2], scale_factor_override: Option<f64>) {
+        self.physical_size = physical_size;
+        let [window_width, window_height] = crate::window_size(physical_size, self.scale_factor, scale_factor_override);
+        self.options.window_width = window_width;
+        self.options.window_height = window_height;
+        log::debug!("resized a window to {}x{} ({}x{} physical pixels)", window_width, window_height, physical_size[0], physical_size[1]);
+
+        for layer in self.base_layers.iter_mut() {
+            layer.resize(window_width, window_height);
+        }
+        for layer in self.mask_layers.iter_mut() {
+            layer.resize(window_width, window_height);
+        }
+        if let Some(cursor_image) = self.cursor_image.as_mut() {
+            cursor_image.batch.resize(window_width, window_height);
+        }
+    }
+
+    // the mouse position in logical pixels
+    pub fn mouse_position(&self, scale_factor_override: Option<f64>) -> [f32; 2] {
+        let scale_factor = self.scale_factor(scale_factor_override);
+        [(self.cursor_position[0] / scale_factor) as f32, (self.cursor_position[1] / scale_factor) as f32]
+    }
+
+    // starts the next mask layer, base content drawn after its end_mask goes to a new base layer on top of it
+    pub fn begin_mask(&mut self, programs: &Programs) {
+        let (window_width, window_height) = (self.options.window_width, self.options.window_height);
+        if self.used_mask_layers == self.mask_layers.len() {
+            let mask_layer = MaskLayer::new(window_width, window_height, programs.shape.clone(), &self.base_layers[0].instances.shapes);
+            self.mask_layers.push(mask_layer);
+        }
+        self.draw_target = DrawTarget::Mask(self.used_mask_layers);
+        self.used_mask_layers += 1;
+        if self.used_mask_layers == self.base_layers.len() {
+            let instance_batch = self.base_layers[0].instances.shapes.share(None);
+            self.base_layers.push(BaseLayer::new(window_width, window_height, programs, instance_batch));
+        }
+    }
+
+    // the instances that instance draw calls currently go to, like shape_batch
+    pub fn instances(&mut self) -> &mut Instances {
+        match self.draw_target {
+            DrawTarget::Base => &mut self.base_layers[self.used_mask_layers].instances,
+            DrawTarget::Mask(layer) => &mut self.mask_layers[layer].mask_instances,
+            DrawTarget::Masked(layer) => &mut self.mask_layers[layer].instances
+        }
+    }
+
+    // the shape batch that draw calls currently go to, depending on begin_mask / mask_inside / end_mask
+    pub fn shape_batch(&mut self) -> &mut Batch {
+        match self.draw_target {
+            DrawTarget::Base => &mut self.base_layers[self.used_mask_layers].batch,
+            DrawTarget::Mask(layer) => &mut self.mask_layers[layer].mask,
+            DrawTarget::Masked(layer) => &mut self.mask_layers[layer].batch
+        }
+    }
+
+    // the texture batch that draw calls for this texture currently go to, mask layers keep their own
+    // texture batches but share the texture
+    pub fn texture_batch(&mut self, handle: TextureHandle, tiled: bool, programs: &Programs, texture: Rc<Texture>) -> &mut TextureBatch {
+        let texture_batches = match self.draw_target {
+            DrawTarget::Base => &mut self.base_layers[self.used_mask_layers].texture_batches,
+            DrawTarget::Mask(layer) => &mut self.mask_layers[layer].mask_textures,
+            DrawTarget::Masked(layer) => &mut self.mask_layers[layer].texture_batches
+        };
+        let (window_width, window_height) = (self.options.window_width, self.options.window_height);
+        texture_batches.get_or_insert_with((handle, tiled), || {
+            let mut texture_batch = TextureBatch::with_texture(window_width, window_height, programs.texture.clone(), texture);
+            texture_batch.tiled = tiled;
+            texture_batch
+        })
+    }
+
+    pub fn replace_texture(&mut self, old: &Rc<Texture>, texture: &Rc<Texture>) {
+        let base_batches = self.base_layers.iter_mut().flat_map(|layer| layer.texture_batches.iter_mut());
+        let mask_batches = self.mask_layers.iter_mut().flat_map(|layer| layer.mask_textures.iter_mut().chain(layer.texture_batches.iter_mut()));
+        for texture_batch in base_batches.chain(mask_batches) {
+            if Rc::ptr_eq(&texture_batch.texture, old) {
+                texture_batch.texture = texture.clone();
+            }
+        }
+        let base_instances = self.base_layers.iter_mut().map(|layer| &mut layer.instances);
+        let mask_instances = self.mask_layers.iter_mut().flat_map(|layer| [&mut layer.mask_instances, &mut layer.instances]);
+        for instances in base_instances.chain(mask_instances) {
+            instances.replace_texture(old, texture);
+        }
+    }
+
+    pub fn remove_texture(&mut self, handle: TextureHandle) {
+        for layer in self.base_layers.iter_mut() {
+            layer.texture_batches.retain(|key| key.0 != handle);
+            layer.instances.remove_texture(handle);
+        }
+        for layer in self.mask_layers.iter_mut() {
+            layer.mask_textures.retain(|key| key.0 != handle);
+            layer.texture_batches.retain(|key| key.0 != handle);
+            layer.mask_instances.remove_texture(handle);
+            layer.instances.remove_texture(handle);
+        }
+    }
+
+    pub fn draw_batches(&mut self, frame: &mut Frame, assets: &Assets, scale_factor_override: Option<f64>, profiler: &mut Profiler) -> Result<(), Error> {
+        let window_size = [self.options.window_width as f32, self.options.window_height as f32];
+        let display = &self.display;
+        let mask_layers = self.mask_layers.iter_mut().take(self.used_mask_layers).map(Some).chain(std::iter::once(None));
+        for (index, (base_layer, mask_layer)) in self.base_layers.iter_mut().zip(mask_layers).enumerate() {
+            base_layer.draw(frame, display, window_size, profiler)?;
+            // every mask layer gets a fresh stencil so layers don't leak into each other
+            if let Some(mask_layer) = mask_layer {
+                profiler.pass(display, || format!("mask {}", index), |query| {
+                    frame.clear_stencil(0);
+                    mask_layer.draw(frame, display, window_size, query)
+                })?;
+            }
+        }
+        self.draw_cursor_image(frame, assets, scale_factor_override, profiler)
+    }
+
+    fn draw_cursor_image(&mut self, frame: &mut Frame, assets: &Assets, scale_factor_override: Option<f64>, profiler: &mut Profiler) -> Result<(), Error> {
+        let position = self.mouse_position(scale_factor_override);
+        let cursor_image = match self.cursor_image.as_mut() {
+            Some(cursor_image) => cursor_image,
+            None => return Ok(())
+        };
+        // picks up hot reloads and unloads of the texture
+        let texture = match assets.get(cursor_image.texture) {
+            Some(texture) => texture,
+            None => return Ok(())
+        };
+        cursor_image.batch.texture = texture;
+
+        let position = [position[0] as i32 - cursor_image.hotspot[0], position[1] as i32 - cursor_image.hotspot[1]];
+        let (texture_width, texture_height) = cursor_image.batch.texture.dimensions();
+        let src_rect = [0, 0, texture_width as i32, texture_height as i32];
+        cursor_image.batch.add_region(position, cursor_image.size[0], cursor_image.size[1], src_rect, &SpriteOptions::default(), true);
+        let display = &self.display;
+        profiler.pass(display, || "cursor".to_string(), |query| {
+            cursor_image.batch.draw_with_params(frame, display, &timed(Batch::get_default_draw_params(), query), 0.0)
+        })
+    }
+}
+
+fn texture_pass_name(texture: &Texture) -> String {
+    match texture.path.as_ref() {
+        Some(path) => format!("texture {}", path),
+        None => "texture".to_string()
+    }
+}
@@ -0,0 +1,161 @@
+use glium::glutin::{dpi::{LogicalPosition, LogicalSize}, event_loop::EventLoopWindowTarget, monitor::MonitorHandle, window::{Fullscreen, Icon, WindowBuilder}, ContextBuilder, NotCurrent};
+use crate::{texture, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    // covers the monitor without changing its video mode
+    Borderless,
+    // switches the monitor to its largest video mode
+    Fullscreen
+}
+
+// everything about the window that has to be decided before it is opened
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    title: String,
+    size: [u32; 2],
+    pub(crate) mode: WindowMode,
+    vsync: bool,
+    samples: u16,
+    resizable: bool,
+    min_size: Option<[u32; 2]>,
+    max_size: Option<[u32; 2]>,
+    position: Option<[i32; 2]>,
+    icon: Option<String>,
+    pub(crate) transparent: bool,
+    always_on_top: bool,
+    decorations: bool
+}
+
+impl WindowConfig {
+    pub fn new(title: &str, width: u32, height: u32) -> Self {
+        Self {
+            title: title.to_string(),
+            size: [width, height],
+            mode: WindowMode::Windowed,
+            vsync: false,
+            samples: 0,
+            resizable: true,
+            min_size: None,
+            max_size: None,
+            position: None,
+            icon: None,
+            transparent: false,
+            always_on_top: false,
+            decorations: true
+        }
+    }
+
+    pub fn mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    // msaa samples per pixel, 0 turns it off
+    pub fn samples(mut self, samples: u16) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn min_size(mut self, width: u32, height: u32) -> Self {
+        self.min_size = Some([width, height]);
+        self
+    }
+
+    pub fn max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = Some([width, height]);
+        self
+    }
+
+    // of the window's top left corner on the desktop, left to the os when not set
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some([x, y]);
+        self
+    }
+
+    // path to an image file, loaded when the window is opened
+    pub fn icon(mut self, path: &str) -> Self {
+        self.icon = Some(path.to_string());
+        self
+    }
+
+    // the clear color is drawn fully transparent, so only what's drawn on top of it is visible
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    pub(crate) fn window_builder<T>(&self, event_loop: &EventLoopWindowTarget<T>) -> Result<WindowBuilder, Error> {
+        let mut window_builder = WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(LogicalSize::new(self.size[0], self.size[1]))
+            .with_resizable(self.resizable)
+            .with_fullscreen(fullscreen(self.mode, event_loop.primary_monitor().or_else(|| event_loop.available_monitors().next())))
+            .with_transparent(self.transparent)
+            .with_always_on_top(self.always_on_top)
+            .with_decorations(self.decorations);
+
+        if let Some([width, height]) = self.min_size {
+            window_builder = window_builder.with_min_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some([width, height]) = self.max_size {
+            window_builder = window_builder.with_max_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some([x, y]) = self.position {
+            window_builder = window_builder.with_position(LogicalPosition::new(x, y));
+        }
+        if let Some(path) = &self.icon {
+            window_builder = window_builder.with_window_icon(Some(load_icon(path)?));
+        }
+        Ok(window_builder)
+    }
+
+    pub(crate) fn context_builder(&self) -> ContextBuilder<'static, NotCurrent> {
+        ContextBuilder::new()
+            .with_depth_buffer(24)
+            .with_stencil_buffer(8)
+            .with_vsync(self.vsync)
+            .with_multisampling(self.samples)
+    }
+}
+
+pub(crate) fn fullscreen(mode: WindowMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Fullscreen => {
+            let video_mode = monitor?.video_modes().max_by_key(|video_mode| {
+                (video_mode.size().width * video_mode.size().height, video_mode.refresh_rate_millihertz())
+            })?;
+            Some(Fullscreen::Exclusive(video_mode))
+        }
+    }
+}
+
+pub(crate) fn load_icon(path: &str) -> Result<Icon, Error> {
+    let image = texture::decode_file(path)?;
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).map_err(|error| Error::Icon(path.to_string(), error))
+}
+